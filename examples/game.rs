@@ -8,7 +8,9 @@
 ///
 use std::sync::{Arc, Mutex};
 
-use mini_cqrs_es::{Cqrs, EventConsumers, QueryRunner, SimpleCqrs, SnapshotAggregateManager};
+use mini_cqrs_es::{
+    Cqrs, EventConsumers, InMemorySnapshotStore, QueryRunner, SimpleCqrs, SnapshotAggregateManager,
+};
 
 #[path = "lib/common_game.rs"]
 mod common_game;
@@ -18,13 +20,14 @@ use common_game::*;
 async fn main() -> mini_cqrs_es::anyhow::Result<()> {
     let store = InMemoryEventStore::new();
     let repo = Arc::new(Mutex::new(InMemoryRepository::new()));
-    let snapshot_store = InMemorySnapshotStore::<GameAggregate>::new();
+    let snapshot_store = InMemorySnapshotStore::new();
 
     let consumers = EventConsumers::new()
         .with(GameMainConsumer::new(repo.clone()))
         .with(PrintEventConsumer {});
 
-    let aggregate_manager = SnapshotAggregateManager::new(snapshot_store);
+    let aggregate_manager =
+        SnapshotAggregateManager::new(snapshot_store).with_event_store(store.clone());
 
     let cqrs = SimpleCqrs::new(aggregate_manager, store, consumers);
 
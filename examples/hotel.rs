@@ -76,7 +76,7 @@ async fn main() -> mini_cqrs_es::anyhow::Result<()> {
 mod tests {
     use super::*;
     use mini_cqrs_es::Command;
-    use mini_cqrs_es::{AggregateManager, CqrsError, EventConsumer, StoredEvent};
+    use mini_cqrs_es::{Aggregate, AggregateManager, CqrsError, EventConsumer, StoredEvent};
     use std::error::Error as StdError;
     use std::fmt;
     use std::str::FromStr;
@@ -474,4 +474,1908 @@ mod tests {
         assert_eq!(error.to_string(), "aggregate target mismatch");
         assert!(StdError::source(&error).is_some());
     }
+
+    struct UnavailableEventStore;
+
+    impl mini_cqrs_es::EventStore for UnavailableEventStore {
+        async fn save_events(
+            &self,
+            _aggregate_type: &str,
+            _aggregate_id: &str,
+            _events: &[mini_cqrs_es::NewEvent],
+            _expected_version: mini_cqrs_es::ExpectedVersion,
+        ) -> Result<Vec<StoredEvent>, CqrsError> {
+            panic!("events must not be saved when loading fails");
+        }
+
+        async fn load_events(
+            &self,
+            _aggregate_type: &str,
+            _aggregate_id: &str,
+        ) -> Result<(Vec<StoredEvent>, u64), CqrsError> {
+            Err(CqrsError::Other(mini_cqrs_es::anyhow::anyhow!(
+                "database is unavailable"
+            )))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_failure_is_propagated_as_event_store_error() {
+        let agg_manager = SimpleAggregateManager::new(UnavailableEventStore);
+        let cqrs = SimpleCqrs::new(agg_manager, UnavailableEventStore, EventConsumers::new());
+
+        let result = cqrs
+            .execute(&HotelId::new(1), &CmdInitializeHotel { room_count: 3 })
+            .await;
+
+        assert!(matches!(result.unwrap_err(), CqrsError::EventStore(_)));
+    }
+
+    #[tokio::test]
+    async fn test_load_missing_stream_yields_default_aggregate() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        let manager = SimpleAggregateManager::new(store);
+        let aggregate: HotelAggregate = manager.load(&HotelId::new(99)).await.unwrap();
+
+        assert_eq!(aggregate.aggregate_id(), HotelId::new(99));
+        assert_eq!(aggregate.version(), 0);
+        assert!(aggregate.rooms.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_command_on_missing_aggregate_fails_with_not_found() {
+        let (cqrs, _read_model, hotel_id) = setup().await;
+
+        let result = cqrs
+            .execute(
+                &hotel_id,
+                &CmdCheckIn {
+                    room_number: 1,
+                    guest_name: "Alice".into(),
+                },
+            )
+            .await;
+
+        assert!(matches!(
+            result.unwrap_err(),
+            CqrsError::AggregateNotFound(id) if id == hotel_id.to_string()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_create_command_on_existing_aggregate_fails_with_conflict() {
+        let (cqrs, _read_model, hotel_id) = setup().await;
+
+        cqrs.execute(&hotel_id, &CmdInitializeHotel { room_count: 5 })
+            .await
+            .unwrap();
+
+        let result = cqrs
+            .execute(&hotel_id, &CmdInitializeHotel { room_count: 5 })
+            .await;
+
+        assert!(matches!(
+            result.unwrap_err(),
+            CqrsError::Conflict {
+                expected_version: 0,
+                actual_version: 1
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_save_events_checks_expected_version() {
+        use mini_cqrs_es::{EventMetadata, EventStore, ExpectedVersion, NewEvent};
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        // Event IDs are unique, so every append needs new events.
+        let new_event = || {
+            NewEvent::from_payload(
+                HotelEvent::HotelInitialized { room_count: 1 },
+                EventMetadata::default(),
+            )
+            .unwrap()
+        };
+        let aggregate_type = std::any::type_name::<HotelAggregate>();
+
+        let result = store
+            .save_events(aggregate_type, "1", &[new_event()], ExpectedVersion::StreamExists)
+            .await;
+        assert!(matches!(result, Err(CqrsError::AggregateNotFound(_))));
+
+        store
+            .save_events(aggregate_type, "1", &[new_event()], ExpectedVersion::NoStream)
+            .await
+            .unwrap();
+
+        let result = store
+            .save_events(aggregate_type, "1", &[new_event()], ExpectedVersion::Exact(0))
+            .await;
+        assert!(matches!(result, Err(CqrsError::Conflict { .. })));
+
+        let saved = store
+            .save_events(aggregate_type, "1", &[new_event()], ExpectedVersion::Any)
+            .await
+            .unwrap();
+        assert_eq!(saved[0].version, 2);
+    }
+
+    #[tokio::test]
+    async fn test_load_at_version_and_as_of() {
+        use chrono::{Duration, TimeZone, Utc};
+        use mini_cqrs_es::FixedClock;
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let clock = Arc::new(FixedClock::new(start));
+        let agg_manager = SimpleAggregateManager::new(store.clone());
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), EventConsumers::new())
+            .with_clock(clock.clone());
+        let hotel_id = HotelId::new(1);
+
+        cqrs.execute(&hotel_id, &CmdInitializeHotel { room_count: 2 })
+            .await
+            .unwrap();
+        cqrs.execute(
+            &hotel_id,
+            &CmdCheckIn {
+                room_number: 1,
+                guest_name: "Alice".into(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let before_bob = start + Duration::minutes(30);
+        clock.advance(Duration::hours(1));
+
+        cqrs.execute(
+            &hotel_id,
+            &CmdCheckIn {
+                room_number: 2,
+                guest_name: "Bob".into(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let manager = SimpleAggregateManager::new(store);
+
+        let at_v1: HotelAggregate = manager.load_at_version(&hotel_id, 1).await.unwrap();
+        assert_eq!(at_v1.version(), 1);
+        assert_eq!(at_v1.rooms.get(&1), Some(&RoomState::Free));
+
+        let as_of: HotelAggregate = manager.load_as_of(&hotel_id, before_bob).await.unwrap();
+        assert_eq!(as_of.version(), 2);
+        assert_eq!(
+            as_of.rooms.get(&1),
+            Some(&RoomState::Occupied {
+                guest_name: "Alice".into()
+            })
+        );
+        assert_eq!(as_of.rooms.get(&2), Some(&RoomState::Free));
+
+        let before_creation: HotelAggregate = manager
+            .load_as_of(&hotel_id, start - Duration::days(1))
+            .await
+            .unwrap();
+        assert_eq!(before_creation.version(), 0);
+        assert!(before_creation.rooms.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unit_of_work_commits_streams_atomically() {
+        use mini_cqrs_es::{EventMetadata, EventStore, ExpectedVersion, NewEvent, UnitOfWork};
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        let from = HotelId::new(1);
+        let to = HotelId::new(2);
+        let event = |payload: HotelEvent| {
+            NewEvent::from_payload(payload, EventMetadata::default()).unwrap()
+        };
+
+        // Transfer a guest between hotels: both streams are created in one commit.
+        let unit = UnitOfWork::new()
+            .append_for::<HotelAggregate>(
+                &from,
+                vec![
+                    event(HotelEvent::HotelInitialized { room_count: 1 }),
+                    event(HotelEvent::GuestCheckedOut { room_number: 1 }),
+                ],
+                ExpectedVersion::NoStream,
+            )
+            .append_for::<HotelAggregate>(
+                &to,
+                vec![
+                    event(HotelEvent::HotelInitialized { room_count: 1 }),
+                    event(HotelEvent::GuestCheckedIn {
+                        room_number: 1,
+                        stay_id: "2-2".into(),
+                        guest_name: "Alice".into(),
+                    }),
+                ],
+                ExpectedVersion::NoStream,
+            );
+
+        let persisted = store.save_unit_of_work(&unit).await.unwrap();
+        assert_eq!(persisted.len(), 2);
+        assert_eq!(persisted[1].last().unwrap().version, 2);
+
+        // The second append conflicts, so the first one must be rolled back too.
+        let unit = UnitOfWork::new()
+            .append_for::<HotelAggregate>(
+                &from,
+                vec![event(HotelEvent::GuestCheckedOut { room_number: 1 })],
+                ExpectedVersion::Exact(2),
+            )
+            .append_for::<HotelAggregate>(
+                &to,
+                vec![event(HotelEvent::GuestCheckedOut { room_number: 1 })],
+                ExpectedVersion::Exact(1),
+            );
+
+        let result = store.save_unit_of_work(&unit).await;
+        assert!(matches!(
+            result,
+            Err(CqrsError::Conflict {
+                expected_version: 1,
+                actual_version: 2
+            })
+        ));
+
+        let manager = SimpleAggregateManager::new(store);
+        let from_hotel: HotelAggregate = manager.load(&from).await.unwrap();
+        let to_hotel: HotelAggregate = manager.load(&to).await.unwrap();
+        assert_eq!(from_hotel.version(), 2);
+        assert_eq!(to_hotel.version(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_outbox_relay_publishes_committed_events() {
+        use mini_cqrs_es::{InMemoryPublisher, OutboxRelay, OutboxStore};
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool).with_outbox();
+        store.create_table().await.unwrap();
+
+        let agg_manager = SimpleAggregateManager::new(store.clone());
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), EventConsumers::new());
+        let hotel_id = HotelId::new(1);
+
+        cqrs.execute(&hotel_id, &CmdInitializeHotel { room_count: 2 })
+            .await
+            .unwrap();
+        cqrs.execute(
+            &hotel_id,
+            &CmdCheckIn {
+                room_number: 1,
+                guest_name: "Alice".into(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let publisher = InMemoryPublisher::new();
+        let relay = OutboxRelay::new(store.clone(), publisher.clone()).with_batch_size(1);
+
+        assert_eq!(relay.relay_pending().await.unwrap(), 1);
+        assert_eq!(relay.relay_pending().await.unwrap(), 1);
+        assert_eq!(relay.relay_pending().await.unwrap(), 0);
+
+        let published = publisher.published();
+        assert_eq!(published.len(), 2);
+        assert_eq!(published[0].event_type, "HotelInitialized");
+        assert_eq!(published[1].event_type, "GuestCheckedIn");
+        assert!(store.pending(10).await.unwrap().is_empty());
+    }
+
+    struct UnreachablePublisher;
+
+    impl mini_cqrs_es::Publisher for UnreachablePublisher {
+        async fn publish(&self, _event: &StoredEvent) -> Result<(), CqrsError> {
+            Err(CqrsError::Other(mini_cqrs_es::anyhow::anyhow!(
+                "broker is unreachable"
+            )))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_outbox_keeps_events_pending_when_publishing_fails() {
+        use mini_cqrs_es::{OutboxRelay, OutboxStore};
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool).with_outbox();
+        store.create_table().await.unwrap();
+
+        let agg_manager = SimpleAggregateManager::new(store.clone());
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), EventConsumers::new());
+
+        cqrs.execute(&HotelId::new(1), &CmdInitializeHotel { room_count: 2 })
+            .await
+            .unwrap();
+
+        let relay = OutboxRelay::new(store.clone(), UnreachablePublisher);
+
+        assert!(relay.relay_pending().await.is_err());
+        assert_eq!(store.pending(10).await.unwrap().len(), 1);
+    }
+
+    struct CmdInitializeHotelAs {
+        actor: &'static str,
+        room_count: u32,
+    }
+
+    impl Command for CmdInitializeHotelAs {
+        type Aggregate = HotelAggregate;
+
+        async fn handle(&self, aggregate: &Self::Aggregate) -> Result<Vec<HotelEvent>, CqrsError> {
+            CmdInitializeHotel {
+                room_count: self.room_count,
+            }
+            .handle(aggregate)
+            .await
+        }
+
+        fn metadata(&self) -> mini_cqrs_es::EventMetadata {
+            mini_cqrs_es::EventMetadata {
+                actor: Some(self.actor.to_string()),
+                ..Default::default()
+            }
+        }
+    }
+
+    struct AdminOnly;
+
+    impl mini_cqrs_es::CommandMiddleware for AdminOnly {
+        async fn before_handle(
+            &self,
+            context: &mut mini_cqrs_es::CommandContext,
+        ) -> Result<(), CqrsError> {
+            if context.metadata.actor.as_deref() != Some("admin") {
+                return Err(CqrsError::invariant("only admins can execute commands"));
+            }
+            context.metadata.correlation_id = Some("correlation-1".to_string());
+            Ok(())
+        }
+    }
+
+    fn dry_run(context: &mini_cqrs_es::CommandContext) -> &'static str {
+        if context.dry_run { " (dry run)" } else { "" }
+    }
+
+    #[derive(Clone, Default)]
+    struct HookRecorder {
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl mini_cqrs_es::CommandMiddleware for HookRecorder {
+        async fn before_handle(
+            &self,
+            context: &mut mini_cqrs_es::CommandContext,
+        ) -> Result<(), CqrsError> {
+            self.calls.lock().unwrap().push(format!(
+                "before_handle {}{}",
+                context.aggregate_id,
+                dry_run(context)
+            ));
+            Ok(())
+        }
+
+        async fn after_handle(
+            &self,
+            context: &mini_cqrs_es::CommandContext,
+            events: &[mini_cqrs_es::NewEvent],
+        ) -> Result<(), CqrsError> {
+            self.calls.lock().unwrap().push(format!(
+                "after_handle {}{}",
+                events[0].event_type,
+                dry_run(context)
+            ));
+            Ok(())
+        }
+
+        async fn after_commit(
+            &self,
+            _context: &mini_cqrs_es::CommandContext,
+            events: &[StoredEvent],
+        ) -> Result<(), CqrsError> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("after_commit {}", events[0].version));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middlewares_run_around_command_execution() {
+        use mini_cqrs_es::EventStore;
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        let recorder = HookRecorder::default();
+        let agg_manager = SimpleAggregateManager::new(store.clone());
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), EventConsumers::new())
+            .with_middleware(AdminOnly)
+            .with_middleware(recorder.clone());
+        let hotel_id = HotelId::new(1);
+
+        let result = cqrs
+            .execute(
+                &hotel_id,
+                &CmdInitializeHotelAs {
+                    actor: "guest",
+                    room_count: 2,
+                },
+            )
+            .await;
+        assert!(matches!(result, Err(CqrsError::CommandInvariant(_))));
+        assert!(recorder.calls.lock().unwrap().is_empty());
+
+        cqrs.execute(
+            &hotel_id,
+            &CmdInitializeHotelAs {
+                actor: "admin",
+                room_count: 2,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            *recorder.calls.lock().unwrap(),
+            vec![
+                "before_handle 1".to_string(),
+                "after_handle HotelInitialized".to_string(),
+                "after_commit 1".to_string(),
+            ]
+        );
+
+        let (events, _) = store
+            .load_events(std::any::type_name::<HotelAggregate>(), "1")
+            .await
+            .unwrap();
+        assert_eq!(events[0].metadata.actor.as_deref(), Some("admin"));
+        assert_eq!(
+            events[0].metadata.correlation_id.as_deref(),
+            Some("correlation-1")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_registry_dispatches_serialized_commands() {
+        use mini_cqrs_es::CommandRegistry;
+        use serde_json::json;
+
+        let (cqrs, read_model, _hotel_id) = setup().await;
+        let registry = CommandRegistry::new()
+            .register::<CmdInitializeHotel>("InitializeHotel")
+            .register::<CmdCheckIn>("CheckIn");
+
+        assert!(registry.contains("CheckIn"));
+        assert_eq!(
+            registry.aggregate_type("CheckIn"),
+            Some(std::any::type_name::<HotelAggregate>())
+        );
+
+        registry
+            .dispatch(&cqrs, "3", json!({ "type": "InitializeHotel", "room_count": 2 }))
+            .await
+            .unwrap();
+        let result = registry
+            .dispatch(
+                &cqrs,
+                "3",
+                json!({ "type": "CheckIn", "room_number": 2, "guest_name": "Alice" }),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.command_name, "CheckIn");
+        assert_eq!(result.aggregate_id, "3");
+        let state = cqrs
+            .query(&GetHotelStateQuery::new(read_model.clone()))
+            .await;
+        assert_eq!(
+            state.rooms.get(&2),
+            Some(&RoomState::Occupied {
+                guest_name: "Alice".into()
+            })
+        );
+
+        let unknown = registry
+            .dispatch(&cqrs, "3", json!({ "type": "CheckOut", "room_number": 2 }))
+            .await;
+        assert!(matches!(unknown, Err(CqrsError::CommandInvariant(_))));
+
+        let invalid_id = registry
+            .dispatch(
+                &cqrs,
+                "not-a-number",
+                json!({ "type": "InitializeHotel", "room_count": 1 }),
+            )
+            .await;
+        assert!(matches!(invalid_id, Err(CqrsError::CommandInvariant(_))));
+
+        let malformed = registry
+            .dispatch(&cqrs, "3", json!({ "type": "CheckIn", "room_number": "two" }))
+            .await;
+        assert!(matches!(malformed, Err(CqrsError::Serialization(_))));
+    }
+
+    // Checks a guest into the first free room, replying with the assigned room number.
+    struct CmdCheckInAnyRoom {
+        guest_name: String,
+    }
+
+    impl mini_cqrs_es::CommandWithReply for CmdCheckInAnyRoom {
+        type Aggregate = HotelAggregate;
+        type Reply = u32;
+
+        async fn handle_with_reply(
+            &self,
+            aggregate: &Self::Aggregate,
+        ) -> Result<(Vec<HotelEvent>, u32), CqrsError> {
+            let room_number = (1..=aggregate.rooms.len() as u32)
+                .find(|n| aggregate.rooms.get(n) == Some(&RoomState::Free))
+                .ok_or_else(|| CqrsError::domain("No free rooms"))?;
+
+            Ok((
+                vec![HotelEvent::GuestCheckedIn {
+                    room_number,
+                    stay_id: format!("{}-{}", aggregate.aggregate_id(), aggregate.version() + 1),
+                    guest_name: self.guest_name.clone(),
+                }],
+                room_number,
+            ))
+        }
+
+        fn expected_version(&self) -> mini_cqrs_es::ExpectedVersion {
+            mini_cqrs_es::ExpectedVersion::StreamExists
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_reply_returns_reply_and_outcome() {
+        let (cqrs, _read_model, hotel_id) = setup().await;
+
+        cqrs.execute(&hotel_id, &CmdInitializeHotel { room_count: 2 })
+            .await
+            .unwrap();
+        cqrs.execute(
+            &hotel_id,
+            &CmdCheckIn {
+                room_number: 1,
+                guest_name: "Alice".into(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let outcome = cqrs
+            .execute_with_reply(
+                &hotel_id,
+                &CmdCheckInAnyRoom {
+                    guest_name: "Bob".into(),
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.reply, 2);
+        assert_eq!(outcome.aggregate_id, hotel_id);
+        assert_eq!(outcome.version, 3);
+        assert_eq!(outcome.events.len(), 1);
+        assert_eq!(outcome.events[0].event_type, "GuestCheckedIn");
+
+        // Reply commands can still be executed as plain commands.
+        let result = cqrs
+            .execute(
+                &hotel_id,
+                &CmdCheckInAnyRoom {
+                    guest_name: "Charlie".into(),
+                },
+            )
+            .await;
+        assert!(matches!(result, Err(CqrsError::Domain(_))));
+    }
+
+    #[tokio::test]
+    async fn test_simulate_previews_command_without_persisting() {
+        use mini_cqrs_es::EventStore;
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        let read_model = Arc::new(Mutex::new(HotelReadModel::default()));
+        let consumers =
+            EventConsumers::new().with(HotelProjectionConsumer::new(read_model.clone()));
+        let recorder = HookRecorder::default();
+        let agg_manager = SimpleAggregateManager::new(store.clone());
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), consumers)
+            .with_middleware(recorder.clone());
+        let hotel_id = HotelId::new(1);
+
+        cqrs.execute(&hotel_id, &CmdInitializeHotel { room_count: 2 })
+            .await
+            .unwrap();
+        recorder.calls.lock().unwrap().clear();
+
+        let simulation = cqrs
+            .simulate(
+                &hotel_id,
+                &CmdCheckIn {
+                    room_number: 1,
+                    guest_name: "Alice".into(),
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(simulation.events.len(), 1);
+        assert_eq!(simulation.events[0].event_type, "GuestCheckedIn");
+        assert_eq!(simulation.aggregate.version(), 2);
+        assert_eq!(
+            *recorder.calls.lock().unwrap(),
+            [
+                "before_handle 1 (dry run)",
+                "after_handle GuestCheckedIn (dry run)"
+            ]
+        );
+        assert_eq!(
+            simulation.aggregate.rooms.get(&1),
+            Some(&RoomState::Occupied {
+                guest_name: "Alice".into()
+            })
+        );
+
+        let (events, version) = store
+            .load_events(std::any::type_name::<HotelAggregate>(), "1")
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(version, 1);
+        let state = cqrs
+            .query(&GetHotelStateQuery::new(read_model.clone()))
+            .await;
+        assert_eq!(state.rooms.get(&1), Some(&RoomState::Free));
+
+        let rejected = cqrs
+            .simulate(&hotel_id, &CmdCheckOut { room_number: 2 })
+            .await;
+        assert!(matches!(rejected, Err(CqrsError::Domain(_))));
+    }
+
+    #[tokio::test]
+    async fn test_serialized_cqrs_avoids_conflicts_on_hot_aggregates() {
+        use mini_cqrs_es::SerializedCqrs;
+        use sqlx::sqlite::SqlitePoolOptions;
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        let agg_manager = SimpleAggregateManager::new(store.clone());
+        let cqrs = SerializedCqrs::new(SimpleCqrs::new(
+            agg_manager,
+            store.clone(),
+            EventConsumers::new(),
+        ));
+        let hotel_id = HotelId::new(1);
+
+        cqrs.execute(&hotel_id, &CmdInitializeHotel { room_count: 3 })
+            .await
+            .unwrap();
+
+        let commands: Vec<CmdCheckIn> = (1..=3)
+            .map(|room_number| CmdCheckIn {
+                room_number,
+                guest_name: format!("Guest {room_number}"),
+            })
+            .collect();
+        let (first, second, third) = tokio::join!(
+            cqrs.execute(&hotel_id, &commands[0]),
+            cqrs.execute(&hotel_id, &commands[1]),
+            cqrs.execute(&hotel_id, &commands[2]),
+        );
+
+        assert!(first.is_ok() && second.is_ok() && third.is_ok());
+        assert_eq!(cqrs.active_locks(), 0);
+
+        let hotel: HotelAggregate = SimpleAggregateManager::new(store)
+            .load(&hotel_id)
+            .await
+            .unwrap();
+        assert_eq!(hotel.version(), 4);
+    }
+
+    #[derive(Clone)]
+    struct CountingEventStore {
+        inner: SqliteEventStore,
+        loads: Arc<Mutex<usize>>,
+        saves: Arc<Mutex<usize>>,
+        version_checks: Arc<Mutex<usize>>,
+    }
+
+    impl CountingEventStore {
+        async fn new() -> Self {
+            let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+            let inner = SqliteEventStore::new(pool);
+            inner.create_table().await.unwrap();
+
+            Self {
+                inner,
+                loads: Arc::new(Mutex::new(0)),
+                saves: Arc::new(Mutex::new(0)),
+                version_checks: Arc::new(Mutex::new(0)),
+            }
+        }
+    }
+
+    impl mini_cqrs_es::EventStore for CountingEventStore {
+        async fn save_events(
+            &self,
+            aggregate_type: &str,
+            aggregate_id: &str,
+            events: &[mini_cqrs_es::NewEvent],
+            expected_version: mini_cqrs_es::ExpectedVersion,
+        ) -> Result<Vec<StoredEvent>, CqrsError> {
+            *self.saves.lock().unwrap() += 1;
+            self.inner
+                .save_events(aggregate_type, aggregate_id, events, expected_version)
+                .await
+        }
+
+        async fn load_events(
+            &self,
+            aggregate_type: &str,
+            aggregate_id: &str,
+        ) -> Result<(Vec<StoredEvent>, u64), CqrsError> {
+            *self.loads.lock().unwrap() += 1;
+            self.inner.load_events(aggregate_type, aggregate_id).await
+        }
+
+        async fn stream_version(
+            &self,
+            aggregate_type: &str,
+            aggregate_id: &str,
+        ) -> Result<u64, CqrsError> {
+            *self.version_checks.lock().unwrap() += 1;
+            self.inner
+                .stream_version(aggregate_type, aggregate_id)
+                .await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_aggregate_manager_skips_replay_and_revalidates() {
+        use mini_cqrs_es::CachedAggregateManager;
+
+        let store = CountingEventStore::new().await;
+        let sqlite = store.inner.clone();
+
+        let agg_manager =
+            CachedAggregateManager::new(SimpleAggregateManager::new(store.clone()), store.clone());
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), EventConsumers::new());
+        let hotel_id = HotelId::new(1);
+
+        cqrs.execute(&hotel_id, &CmdInitializeHotel { room_count: 3 })
+            .await
+            .unwrap();
+        let loads_after_init = *store.loads.lock().unwrap();
+
+        for room_number in 1..=2 {
+            cqrs.execute(
+                &hotel_id,
+                &CmdCheckIn {
+                    room_number,
+                    guest_name: format!("Guest {room_number}"),
+                },
+            )
+            .await
+            .unwrap();
+        }
+        assert_eq!(*store.loads.lock().unwrap(), loads_after_init);
+
+        // Another writer appends to the stream behind the cache's back.
+        let other = SimpleCqrs::new(
+            SimpleAggregateManager::new(sqlite.clone()),
+            sqlite,
+            EventConsumers::new(),
+        );
+        other
+            .execute(
+                &hotel_id,
+                &CmdCheckIn {
+                    room_number: 3,
+                    guest_name: "Carol".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+
+        // The stale copy is detected and the aggregate is replayed from the store.
+        let result = cqrs
+            .execute(
+                &hotel_id,
+                &CmdCheckIn {
+                    room_number: 3,
+                    guest_name: "Dave".to_string(),
+                },
+            )
+            .await;
+        assert!(matches!(result, Err(CqrsError::Domain(_))));
+        assert_eq!(*store.loads.lock().unwrap(), loads_after_init + 1);
+    }
+
+    #[tokio::test]
+    async fn test_cached_aggregate_manager_without_revalidation() {
+        use mini_cqrs_es::CachedAggregateManager;
+
+        let store = CountingEventStore::new().await;
+        let sqlite = store.inner.clone();
+
+        let agg_manager =
+            CachedAggregateManager::new(SimpleAggregateManager::new(store.clone()), store.clone())
+                .with_revalidation(false);
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), EventConsumers::new());
+        let hotel_id = HotelId::new(1);
+        let check_in = |room_number: u32| CmdCheckIn {
+            room_number,
+            guest_name: format!("Guest {room_number}"),
+        };
+
+        cqrs.execute(&hotel_id, &CmdInitializeHotel { room_count: 3 })
+            .await
+            .unwrap();
+        let loads = *store.loads.lock().unwrap();
+        let version_checks = *store.version_checks.lock().unwrap();
+
+        cqrs.execute(&hotel_id, &check_in(1)).await.unwrap();
+        assert_eq!(*store.loads.lock().unwrap(), loads);
+        assert_eq!(*store.version_checks.lock().unwrap(), version_checks);
+
+        // Writes behind the cache's back are only caught by the optimistic concurrency check.
+        let other = SimpleCqrs::new(
+            SimpleAggregateManager::new(sqlite.clone()),
+            sqlite,
+            EventConsumers::new(),
+        );
+        other.execute(&hotel_id, &check_in(2)).await.unwrap();
+
+        let result = cqrs.execute(&hotel_id, &check_in(3)).await;
+        assert!(matches!(result, Err(CqrsError::Conflict { .. })));
+        assert_eq!(*store.loads.lock().unwrap(), loads);
+    }
+
+    #[tokio::test]
+    async fn test_cached_aggregate_manager_evicts_least_recently_used() {
+        use mini_cqrs_es::CachedAggregateManager;
+
+        let store = CountingEventStore::new().await;
+        let agg_manager =
+            CachedAggregateManager::new(SimpleAggregateManager::new(store.clone()), store.clone())
+                .with_capacity(2);
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), EventConsumers::new());
+        let hotels = [HotelId::new(1), HotelId::new(2), HotelId::new(3)];
+        let check_in = |room_number: u32| CmdCheckIn {
+            room_number,
+            guest_name: format!("Guest {room_number}"),
+        };
+
+        for hotel_id in &hotels[..2] {
+            cqrs.execute(hotel_id, &CmdInitializeHotel { room_count: 3 })
+                .await
+                .unwrap();
+        }
+        cqrs.execute(&hotels[0], &check_in(1)).await.unwrap();
+        cqrs.execute(&hotels[2], &CmdInitializeHotel { room_count: 3 })
+            .await
+            .unwrap();
+
+        // The second hotel was used least recently and is the only one evicted.
+        let loads = *store.loads.lock().unwrap();
+        cqrs.execute(&hotels[0], &check_in(2)).await.unwrap();
+        cqrs.execute(&hotels[2], &check_in(1)).await.unwrap();
+        assert_eq!(*store.loads.lock().unwrap(), loads);
+        cqrs.execute(&hotels[1], &check_in(1)).await.unwrap();
+        assert_eq!(*store.loads.lock().unwrap(), loads + 1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_batch_groups_commands_per_aggregate() {
+        let store = CountingEventStore::new().await;
+        let agg_manager = SimpleAggregateManager::new(store.clone());
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), EventConsumers::new());
+        let (first_hotel, second_hotel) = (HotelId::new(1), HotelId::new(2));
+
+        cqrs.execute(&first_hotel, &CmdInitializeHotel { room_count: 3 })
+            .await
+            .unwrap();
+        let (loads, saves) = (*store.loads.lock().unwrap(), *store.saves.lock().unwrap());
+
+        let check_in = |room_number: u32, guest_name: &str| CmdCheckIn {
+            room_number,
+            guest_name: guest_name.to_string(),
+        };
+        let commands = vec![
+            (first_hotel.clone(), check_in(1, "Alice")),
+            (second_hotel.clone(), check_in(1, "Bob")),
+            (first_hotel.clone(), check_in(1, "Carol")),
+            (first_hotel.clone(), check_in(2, "Dave")),
+        ];
+
+        let outcome = cqrs.execute_batch(&commands).await;
+        let results = outcome.results;
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap(), &first_hotel);
+        assert!(matches!(results[1], Err(CqrsError::AggregateNotFound(_))));
+        // The second check-in sees the first one, although nothing is saved yet.
+        assert!(matches!(results[2], Err(CqrsError::Domain(_))));
+        assert_eq!(results[3].as_ref().unwrap(), &first_hotel);
+
+        // Each aggregate is loaded once, and only the first one has events to save.
+        assert_eq!(*store.loads.lock().unwrap(), loads + 2);
+        assert_eq!(*store.saves.lock().unwrap(), saves + 1);
+
+        let hotel: HotelAggregate = SimpleAggregateManager::new(store)
+            .load(&first_hotel)
+            .await
+            .unwrap();
+        assert_eq!(hotel.version(), 3);
+        assert!(outcome.post_commit_errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_execute_batch_reports_post_commit_failures_separately() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        let consumers = EventConsumers::new().with(FailingConsumer);
+        let agg_manager = SimpleAggregateManager::new(store.clone());
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), consumers);
+        let hotel_id = HotelId::new(1);
+
+        let commands = vec![
+            (hotel_id.clone(), CmdInitializeHotel { room_count: 3 }),
+            (hotel_id.clone(), CmdInitializeHotel { room_count: 3 }),
+        ];
+        let outcome = cqrs.execute_batch(&commands).await;
+
+        // The events are saved, so the commands that emitted them succeed.
+        assert_eq!(outcome.results[0].as_ref().unwrap(), &hotel_id);
+        assert!(outcome.results[1].is_err());
+        assert_eq!(outcome.post_commit_errors.len(), 1);
+        let (failed_id, error) = &outcome.post_commit_errors[0];
+        assert_eq!(failed_id, &hotel_id);
+        assert!(matches!(error, CqrsError::Domain(_)));
+
+        let hotel: HotelAggregate = SimpleAggregateManager::new(store)
+            .load(&hotel_id)
+            .await
+            .unwrap();
+        assert_eq!(hotel.version(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_load_reports_event_that_cannot_be_applied() {
+        use mini_cqrs_es::{EventMetadata, EventStore, ExpectedVersion, NewEvent};
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+        let hotel_id = HotelId::new(1);
+
+        let events = [
+            HotelEvent::HotelInitialized { room_count: 2 },
+            HotelEvent::GuestCheckedOut { room_number: 9 },
+        ]
+        .into_iter()
+        .map(|event| NewEvent::from_payload(event, EventMetadata::default()).unwrap())
+        .collect::<Vec<_>>();
+        let stored = store
+            .save_events(
+                std::any::type_name::<HotelAggregate>(),
+                &hotel_id.to_string(),
+                &events,
+                ExpectedVersion::NoStream,
+            )
+            .await
+            .unwrap();
+
+        let result = SimpleAggregateManager::new(store)
+            .load::<HotelAggregate>(&hotel_id)
+            .await;
+
+        match result {
+            Err(CqrsError::EventApply {
+                event_id,
+                version,
+                source,
+            }) => {
+                assert_eq!(event_id, stored[1].id);
+                assert_eq!(version, 2);
+                assert!(matches!(*source, CqrsError::Domain(_)));
+            }
+            other => panic!("expected an EventApply error, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invariant_violation_rejects_command_before_saving() {
+        let (cqrs, _read_model, hotel_id) = setup().await;
+
+        cqrs.execute(&hotel_id, &CmdInitializeHotel { room_count: 2 })
+            .await
+            .unwrap();
+        let check_in = |room_number: u32| CmdCheckIn {
+            room_number,
+            guest_name: "Alice".into(),
+        };
+        cqrs.execute(&hotel_id, &check_in(1)).await.unwrap();
+
+        // The room is free, but the guest already occupies another one.
+        let result = cqrs.execute(&hotel_id, &check_in(2)).await;
+        assert!(matches!(result, Err(CqrsError::CommandInvariant(_))));
+
+        let result = cqrs.simulate(&hotel_id, &check_in(2)).await;
+        assert!(matches!(result, Err(CqrsError::CommandInvariant(_))));
+
+        // Nothing was saved: the stream is still at version 2.
+        let simulation = cqrs
+            .simulate(&hotel_id, &CmdCheckOut { room_number: 1 })
+            .await
+            .unwrap();
+        assert_eq!(simulation.aggregate.version(), 3);
+        assert_eq!(simulation.aggregate.rooms[&2], RoomState::Free);
+    }
+
+    mini_cqrs_es::event_store_conformance!(sqlite_event_store, async {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+        store
+    });
+
+    #[tokio::test]
+    async fn test_sqlite_event_store_lists_streams() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        mini_cqrs_es::testkit::event_store::check_stream_listing(&store).await;
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_event_store_truncates_streams() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        mini_cqrs_es::testkit::event_store::check_truncation(&store).await;
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_event_store_isolates_tenants() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        mini_cqrs_es::testkit::event_store::check_tenant_isolation(&store).await;
+    }
+
+    struct CmdInitializeHotelFor {
+        tenant_id: &'static str,
+    }
+
+    impl Command for CmdInitializeHotelFor {
+        type Aggregate = HotelAggregate;
+
+        async fn handle(&self, aggregate: &Self::Aggregate) -> Result<Vec<HotelEvent>, CqrsError> {
+            CmdInitializeHotel { room_count: 1 }.handle(aggregate).await
+        }
+
+        fn metadata(&self) -> mini_cqrs_es::EventMetadata {
+            mini_cqrs_es::EventMetadata {
+                tenant_id: Some(self.tenant_id.to_string()),
+                ..Default::default()
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_event_store_migrates_tables_created_before_tenants() {
+        use mini_cqrs_es::{EventStore, OutboxStore};
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query(
+            "CREATE TABLE events (
+                id TEXT NOT NULL UNIQUE,
+                aggregate_type TEXT NOT NULL,
+                event_type TEXT NOT NULL,
+                aggregate_id TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                metadata TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                global_sequence INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                UNIQUE (aggregate_type, aggregate_id, version)
+            )",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "CREATE TABLE outbox (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                global_sequence INTEGER NOT NULL REFERENCES events (global_sequence),
+                delivered_at TEXT
+            )",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let aggregate_type = std::any::type_name::<HotelAggregate>();
+        let events = [
+            ("1", r#"{"HotelInitialized":{"room_count":2}}"#, "{}"),
+            (
+                "2",
+                r#"{"HotelInitialized":{"room_count":1}}"#,
+                r#"{"tenant_id":"acme"}"#,
+            ),
+            ("1", r#"{"GuestCheckedOut":{"room_number":1}}"#, "{}"),
+        ];
+        for (i, (aggregate_id, payload, metadata)) in events.into_iter().enumerate() {
+            let version = if i == 2 { 2 } else { 1 };
+            sqlx::query(
+                "INSERT INTO events (id, aggregate_type, event_type, aggregate_id, payload, metadata, version, timestamp)
+                 VALUES (?, ?, 'event', ?, ?, ?, ?, '2026-01-01T00:00:00Z')",
+            )
+            .bind(format!("event-{i}"))
+            .bind(aggregate_type)
+            .bind(aggregate_id)
+            .bind(payload)
+            .bind(metadata)
+            .bind(version)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query("INSERT INTO outbox (global_sequence) VALUES (3)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let store = SqliteEventStore::new(pool).with_outbox();
+        store.create_table().await.unwrap();
+        // Migrating is done once.
+        store.create_table().await.unwrap();
+
+        let (events, version) = store.load_events(aggregate_type, "1").await.unwrap();
+        assert_eq!(version, 2);
+        let sequences: Vec<_> = events.iter().map(|e| e.global_sequence).collect();
+        assert_eq!(sequences, [Some(1), Some(2)]);
+        let (events, _) = store.load_events(aggregate_type, "2").await.unwrap();
+        assert_eq!(events[0].global_sequence, Some(1));
+
+        let pending = store.pending(10).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].event.id, "event-2");
+
+        // New events follow the migrated ones.
+        let cqrs = SimpleCqrs::new(
+            SimpleAggregateManager::new(store.clone()),
+            store.clone(),
+            EventConsumers::new(),
+        );
+        cqrs.execute(&HotelId::new(3), &CmdInitializeHotel { room_count: 1 })
+            .await
+            .unwrap();
+        let (events, _) = store.load_events(aggregate_type, "3").await.unwrap();
+        assert_eq!(events[0].global_sequence, Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_tenant_cqrs_only_reaches_aggregates_of_its_tenant() {
+        use mini_cqrs_es::{EventStore, TenantEventStore};
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        let cqrs_for = |tenant_id: &str| {
+            let store = store.for_tenant(tenant_id);
+            let agg_manager = SimpleAggregateManager::new(store.clone());
+            SimpleCqrs::new(agg_manager, store, EventConsumers::new()).with_tenant(tenant_id)
+        };
+        let (acme, globex) = (cqrs_for("acme"), cqrs_for("globex"));
+        let (acme_hotel, globex_hotel) = (HotelId::new(1), HotelId::new(2));
+
+        acme.execute(&acme_hotel, &CmdInitializeHotel { room_count: 2 })
+            .await
+            .unwrap();
+        globex
+            .execute(&globex_hotel, &CmdInitializeHotel { room_count: 1 })
+            .await
+            .unwrap();
+
+        let check_in = CmdCheckIn {
+            room_number: 1,
+            guest_name: "Mallory".into(),
+        };
+        let result = globex.execute(&acme_hotel, &check_in).await;
+        assert!(matches!(
+            result,
+            Err(CqrsError::TenantMismatch { ref tenant_id, .. }) if tenant_id == "globex"
+        ));
+        let result = globex
+            .execute(&acme_hotel, &CmdInitializeHotel { room_count: 1 })
+            .await;
+        assert!(matches!(result, Err(CqrsError::TenantMismatch { .. })));
+
+        // Commands can't run on behalf of another tenant either.
+        let result = globex
+            .execute(&HotelId::new(3), &CmdInitializeHotelFor { tenant_id: "acme" })
+            .await;
+        assert!(matches!(result, Err(CqrsError::TenantMismatch { .. })));
+
+        let aggregate_type = std::any::type_name::<HotelAggregate>();
+        let (events, version) = store.load_events(aggregate_type, "1").await.unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(events[0].metadata.tenant_id.as_deref(), Some("acme"));
+        assert_eq!(events[0].global_sequence, Some(1));
+
+        let (events, _) = store.load_events(aggregate_type, "2").await.unwrap();
+        assert_eq!(events[0].metadata.tenant_id.as_deref(), Some("globex"));
+        assert_eq!(events[0].global_sequence, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_tenant_cqrs_isolates_tenants_over_unscoped_store() {
+        use mini_cqrs_es::EventStore;
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        // The aggregate manager reads from the unscoped store.
+        let cqrs_for = |tenant_id: &str| {
+            let agg_manager = SimpleAggregateManager::new(store.clone());
+            SimpleCqrs::new(agg_manager, store.clone(), EventConsumers::new())
+                .with_tenant(tenant_id)
+        };
+        let (acme, globex) = (cqrs_for("acme"), cqrs_for("globex"));
+        let acme_hotel = HotelId::new(1);
+
+        acme.execute(&acme_hotel, &CmdInitializeHotel { room_count: 2 })
+            .await
+            .unwrap();
+
+        let check_in = CmdCheckIn {
+            room_number: 1,
+            guest_name: "Mallory".into(),
+        };
+        let result = globex.execute(&acme_hotel, &check_in).await;
+        assert!(matches!(result, Err(CqrsError::TenantMismatch { .. })));
+        let result = globex.simulate(&acme_hotel, &check_in).await;
+        assert!(matches!(result, Err(CqrsError::TenantMismatch { .. })));
+        let outcome = globex.execute_batch(&[(acme_hotel, check_in)]).await;
+        assert!(matches!(
+            outcome.results[0],
+            Err(CqrsError::TenantMismatch { .. })
+        ));
+
+        let aggregate_type = std::any::type_name::<HotelAggregate>();
+        let (events, version) = store.load_events(aggregate_type, "1").await.unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(events[0].metadata.tenant_id.as_deref(), Some("acme"));
+    }
+
+    #[tokio::test]
+    async fn test_projection_harness_feeds_numbered_envelopes() {
+        use mini_cqrs_es::SequentialIdGenerator;
+        use mini_cqrs_es::testkit::ProjectionHarness;
+
+        let read_model = Arc::new(Mutex::new(HotelReadModel::default()));
+        let mut harness = ProjectionHarness::new(HotelProjectionConsumer::new(read_model.clone()))
+            .with_id_generator(SequentialIdGenerator::new("evt"));
+        let (first_hotel, second_hotel) = (HotelId::new(1), HotelId::new(2));
+
+        harness
+            .given::<HotelAggregate>(
+                &first_hotel,
+                [
+                    HotelEvent::HotelInitialized { room_count: 2 },
+                    HotelEvent::GuestCheckedIn {
+                        room_number: 1,
+                        stay_id: "1-2".into(),
+                        guest_name: "Alice".into(),
+                    },
+                ],
+            )
+            .await
+            .unwrap();
+        let fed = harness
+            .given::<HotelAggregate>(
+                &second_hotel,
+                [HotelEvent::HotelInitialized { room_count: 1 }],
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(fed[0].version, 1);
+        assert_eq!(fed[0].event_type, "HotelInitialized");
+        assert_eq!(harness.version::<HotelAggregate>(&first_hotel), 2);
+
+        let sequences: Vec<_> = harness.events().iter().map(|e| e.global_sequence).collect();
+        assert_eq!(sequences, [Some(1), Some(2), Some(3)]);
+        let ids: Vec<_> = harness.events().iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["evt-1", "evt-2", "evt-3"]);
+        assert!(harness.events()[0].timestamp < harness.events()[1].timestamp);
+
+        // The read model isn't keyed by hotel: the last initialization wins.
+        let model = read_model.lock().unwrap();
+        assert_eq!(model.rooms.len(), 1);
+        assert_eq!(model.rooms.get(&1), Some(&RoomState::Free));
+    }
+
+    #[tokio::test]
+    async fn test_injected_clock_and_id_generator_stamp_new_events() {
+        use chrono::{Duration, TimeZone, Utc};
+        use mini_cqrs_es::{EventStore, FixedClock, SequentialIdGenerator};
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let clock = Arc::new(FixedClock::new(start));
+
+        let agg_manager = SimpleAggregateManager::new(store.clone());
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), EventConsumers::new())
+            .with_clock(clock.clone())
+            .with_id_generator(SequentialIdGenerator::new("evt"));
+        let hotel_id = HotelId::new(1);
+
+        cqrs.execute(&hotel_id, &CmdInitializeHotel { room_count: 2 })
+            .await
+            .unwrap();
+        clock.advance(Duration::hours(1));
+        cqrs.execute(
+            &hotel_id,
+            &CmdCheckIn {
+                room_number: 1,
+                guest_name: "Alice".into(),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(cqrs.clock().now(), start + Duration::hours(1));
+
+        let (events, _) = store
+            .load_events(
+                std::any::type_name::<HotelAggregate>(),
+                &hotel_id.to_string(),
+            )
+            .await
+            .unwrap();
+        let ids: Vec<_> = events.iter().map(|e| e.id.as_str()).collect();
+        let timestamps: Vec<_> = events.iter().map(|e| e.timestamp).collect();
+        assert_eq!(ids, ["evt-1", "evt-2"]);
+        assert_eq!(timestamps, [start, start + Duration::hours(1)]);
+    }
+
+    #[tokio::test]
+    async fn test_new_events_get_unique_time_ordered_ids() {
+        use mini_cqrs_es::{EventMetadata, EventStore, ExpectedVersion, NewEvent};
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool);
+        store.create_table().await.unwrap();
+
+        let agg_manager = SimpleAggregateManager::new(store.clone());
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), EventConsumers::new());
+        let hotel_id = HotelId::new(1);
+
+        cqrs.execute(&hotel_id, &CmdInitializeHotel { room_count: 2 })
+            .await
+            .unwrap();
+        for room_number in 1..=2 {
+            cqrs.execute(
+                &hotel_id,
+                &CmdCheckIn {
+                    room_number,
+                    guest_name: format!("Guest {room_number}"),
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        let aggregate_type = std::any::type_name::<HotelAggregate>();
+        let (events, _) = store
+            .load_events(aggregate_type, &hotel_id.to_string())
+            .await
+            .unwrap();
+
+        let ids: Vec<_> = events
+            .iter()
+            .map(|e| uuid::Uuid::parse_str(&e.id).unwrap())
+            .collect();
+        assert!(ids.iter().all(|id| id.get_version_num() == 7));
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // Replaying an already persisted event into another stream is rejected.
+        let mut duplicate = NewEvent::from_payload(
+            HotelEvent::HotelInitialized { room_count: 1 },
+            EventMetadata::default(),
+        )
+        .unwrap();
+        duplicate.id = events[0].id.clone();
+        let result = store
+            .save_events(
+                aggregate_type,
+                &HotelId::new(2).to_string(),
+                &[duplicate],
+                ExpectedVersion::NoStream,
+            )
+            .await;
+        assert!(matches!(result, Err(CqrsError::EventStore(_))));
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_snapshot_store_prunes_old_snapshots() {
+        use mini_cqrs_es::{AggregateSnapshot, SnapshotStore, SqliteSnapshotStore};
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let snapshots = SqliteSnapshotStore::new(pool).with_retention(2);
+        snapshots.create_table().await.unwrap();
+        let hotel_id = HotelId::new(1);
+
+        for version in 1..=3 {
+            let mut hotel = HotelAggregate::default();
+            hotel.set_aggregate_id(hotel_id.clone());
+            hotel.set_version(version);
+            hotel.room_count = version as u32;
+            snapshots
+                .save_snapshot(AggregateSnapshot::new(&hotel, Some(version)).unwrap())
+                .await
+                .unwrap();
+        }
+
+        let history = snapshots
+            .history::<HotelAggregate>(&hotel_id)
+            .await
+            .unwrap();
+        let versions: Vec<_> = history.iter().map(|s| s.version).collect();
+        assert_eq!(versions, [3, 2]);
+        let latest: HotelAggregate = history[0].get_payload().unwrap();
+        assert_eq!(latest.room_count, 3);
+
+        let removed = snapshots
+            .prune::<HotelAggregate>(&hotel_id, 1)
+            .await
+            .unwrap();
+        assert_eq!(removed, 1);
+        let snapshot = snapshots
+            .load_snapshot::<HotelAggregate>(&hotel_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(snapshot.version, 3);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_aggregate_manager_without_event_store() {
+        use mini_cqrs_es::{
+            AggregateManager, AggregateSnapshot, SnapshotAggregateManager, SnapshotStore,
+            SqliteSnapshotStore,
+        };
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let snapshots = SqliteSnapshotStore::new(pool);
+        snapshots.create_table().await.unwrap();
+        let agg_manager = SnapshotAggregateManager::new(snapshots.clone());
+        let hotel_id = HotelId::new(1);
+
+        // Without a snapshot, the aggregate starts from its default state.
+        let hotel: HotelAggregate = agg_manager.load(&hotel_id).await.unwrap();
+        assert_eq!(hotel.aggregate_id(), hotel_id);
+        assert_eq!(hotel.version(), 0);
+
+        let mut hotel = hotel;
+        hotel.room_count = 3;
+        hotel.set_version(4);
+        agg_manager.store(&hotel).await.unwrap();
+        let loaded: HotelAggregate = agg_manager.load(&hotel_id).await.unwrap();
+        assert_eq!(loaded.room_count, 3);
+        assert_eq!(loaded.version(), 4);
+
+        // An incompatible snapshot can't be rebuilt without the events.
+        let snapshot = AggregateSnapshot::<HotelAggregate>::new(&hotel, Some(5)).unwrap();
+        snapshots
+            .save_snapshot(AggregateSnapshot::<HotelAggregate>::from_stored(
+                hotel_id.clone(),
+                snapshot.payload().clone(),
+                5,
+                HotelAggregate::SCHEMA_VERSION + 1,
+            ))
+            .await
+            .unwrap();
+        let result = agg_manager.load::<HotelAggregate>(&hotel_id).await;
+        assert!(matches!(result, Err(CqrsError::SnapshotStore(_))));
+
+        let result = agg_manager
+            .load_at_version::<HotelAggregate>(&hotel_id, 1)
+            .await;
+        assert!(result.is_err());
+    }
+
+    mini_cqrs_es::snapshot_store_conformance!(sqlite_snapshot_store, async {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = mini_cqrs_es::SqliteSnapshotStore::new(pool);
+        store.create_table().await.unwrap();
+        store
+    });
+
+    #[tokio::test]
+    async fn test_stream_archiver_moves_events_preceding_snapshot() {
+        use mini_cqrs_es::{
+            AggregateManager, AggregateSnapshot, EventArchive, EventStore, ReplayVerifier,
+            SnapshotAggregateManager, SnapshotStore, SqliteEventArchive, SqliteSnapshotStore,
+            StreamArchiver,
+        };
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool.clone());
+        store.create_table().await.unwrap();
+        let snapshots = SqliteSnapshotStore::new(pool.clone());
+        snapshots.create_table().await.unwrap();
+        let archive = SqliteEventArchive::new(pool);
+        archive.create_table().await.unwrap();
+
+        let agg_manager =
+            SnapshotAggregateManager::new(snapshots.clone()).with_event_store(store.clone());
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), EventConsumers::new());
+        let hotel_id = HotelId::new(1);
+
+        cqrs.execute(&hotel_id, &CmdInitializeHotel { room_count: 3 })
+            .await
+            .unwrap();
+        for room_number in 1..=2 {
+            cqrs.execute(
+                &hotel_id,
+                &CmdCheckIn {
+                    room_number,
+                    guest_name: format!("Guest {room_number}"),
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        let archiver = StreamArchiver::new(store.clone(), snapshots.clone(), archive.clone());
+        assert_eq!(archiver.archive_all::<HotelAggregate>().await.unwrap(), 2);
+
+        let aggregate_type = std::any::type_name::<HotelAggregate>();
+        let (live, version) = store
+            .load_events(aggregate_type, &hotel_id.to_string())
+            .await
+            .unwrap();
+        assert_eq!(live.len(), 1);
+        assert_eq!(version, 3);
+
+        // The truncated stream keeps working from its snapshot.
+        cqrs.execute(
+            &hotel_id,
+            &CmdCheckIn {
+                room_number: 3,
+                guest_name: "Carol".into(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let history = archiver
+            .load_history::<HotelAggregate>(&hotel_id)
+            .await
+            .unwrap();
+        let versions: Vec<_> = history.iter().map(|e| e.version).collect();
+        assert_eq!(versions, [1, 2, 3, 4]);
+
+        let archived = archive.load_archived_events_after(0, 10).await.unwrap();
+        let sequences: Vec<_> = archived.iter().map(|e| e.global_sequence).collect();
+        assert_eq!(sequences, [Some(1), Some(2)]);
+
+        // Replaying the truncated stream from the event store alone fails.
+        let truncated = |result: Result<HotelAggregate, CqrsError>| {
+            matches!(result, Err(CqrsError::TruncatedStream { version: 3, .. }))
+        };
+        let simple_manager = SimpleAggregateManager::new(store.clone());
+        assert!(truncated(simple_manager.load(&hotel_id).await));
+        assert!(truncated(
+            simple_manager.load_at_version(&hotel_id, 1).await
+        ));
+        assert!(truncated(
+            simple_manager.load_at_version(&hotel_id, 4).await
+        ));
+        assert!(truncated(
+            simple_manager
+                .load_as_of(&hotel_id, chrono::Utc::now())
+                .await
+        ));
+        let verifier = ReplayVerifier::new(store.clone()).with_snapshot_store(snapshots.clone());
+        assert!(matches!(
+            verifier.verify::<HotelAggregate>(&hotel_id).await,
+            Err(CqrsError::TruncatedStream { .. })
+        ));
+
+        // A snapshot that doesn't match the events is never used to truncate a stream.
+        let mut tampered: HotelAggregate = snapshots
+            .load_snapshot::<HotelAggregate>(&hotel_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .get_payload()
+            .unwrap();
+        tampered.room_count = 10;
+        snapshots
+            .save_snapshot(AggregateSnapshot::new(&tampered, Some(4)).unwrap())
+            .await
+            .unwrap();
+
+        let result = archiver.archive::<HotelAggregate>(&hotel_id).await;
+        assert!(matches!(result, Err(CqrsError::SnapshotStore(_))));
+        let (live, _) = store
+            .load_events(aggregate_type, &hotel_id.to_string())
+            .await
+            .unwrap();
+        assert_eq!(live.len(), 2);
+
+        // The snapshot is rebuilt from the whole history.
+        let rebuilt = archiver
+            .rebuild_snapshot::<HotelAggregate>(&hotel_id)
+            .await
+            .unwrap();
+        assert_eq!(rebuilt.room_count, 3);
+        assert_eq!(rebuilt.version(), 4);
+        assert_eq!(
+            archiver.archive::<HotelAggregate>(&hotel_id).await.unwrap(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_archiver_waits_for_outbox_delivery() {
+        use mini_cqrs_es::{
+            EventStore, InMemoryPublisher, OutboxRelay, OutboxStore, SnapshotAggregateManager,
+            SqliteEventArchive, SqliteSnapshotStore, StreamArchiver,
+        };
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let store = SqliteEventStore::new(pool.clone()).with_outbox();
+        store.create_table().await.unwrap();
+        let snapshots = SqliteSnapshotStore::new(pool.clone());
+        snapshots.create_table().await.unwrap();
+        let archive = SqliteEventArchive::new(pool);
+        archive.create_table().await.unwrap();
+
+        let agg_manager =
+            SnapshotAggregateManager::new(snapshots.clone()).with_event_store(store.clone());
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), EventConsumers::new());
+        let hotel_id = HotelId::new(1);
+
+        cqrs.execute(&hotel_id, &CmdInitializeHotel { room_count: 3 })
+            .await
+            .unwrap();
+        for room_number in 1..=2 {
+            cqrs.execute(
+                &hotel_id,
+                &CmdCheckIn {
+                    room_number,
+                    guest_name: format!("Guest {room_number}"),
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        // Events are not deleted before being published.
+        let archiver = StreamArchiver::new(store.clone(), snapshots, archive);
+        let result = archiver.archive::<HotelAggregate>(&hotel_id).await;
+        assert!(matches!(result, Err(CqrsError::EventStore(_))));
+
+        let aggregate_type = std::any::type_name::<HotelAggregate>();
+        let (live, _) = store
+            .load_events(aggregate_type, &hotel_id.to_string())
+            .await
+            .unwrap();
+        assert_eq!(live.len(), 3);
+
+        let relay = OutboxRelay::new(store.clone(), InMemoryPublisher::new());
+        assert_eq!(relay.relay_pending().await.unwrap(), 3);
+
+        assert_eq!(
+            archiver.archive::<HotelAggregate>(&hotel_id).await.unwrap(),
+            2
+        );
+        let (live, version) = store
+            .load_events(aggregate_type, &hotel_id.to_string())
+            .await
+            .unwrap();
+        assert_eq!(live.len(), 1);
+        assert_eq!(version, 3);
+        assert!(store.pending(10).await.unwrap().is_empty());
+
+        let history = archiver
+            .load_history::<HotelAggregate>(&hotel_id)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_key_store_keeps_the_first_key_of_a_subject() {
+        use mini_cqrs_es::{KeyStore, SqliteKeyStore};
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let key_store = SqliteKeyStore::new(pool);
+        key_store.create_table().await.unwrap();
+
+        let key = key_store.load_or_create_key("1-2").await.unwrap();
+        assert_eq!(key_store.load_or_create_key("1-2").await.unwrap(), key);
+        assert_eq!(key_store.load_key("1-2").await.unwrap(), Some(key.clone()));
+        assert_ne!(key_store.load_or_create_key("1-3").await.unwrap(), key);
+    }
+
+    #[tokio::test]
+    async fn test_forgotten_stay_makes_guest_name_unreadable() {
+        use mini_cqrs_es::{
+            EncryptedEventStore, EventPayload, EventStore, PersonalData, SqliteKeyStore,
+        };
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let inner = SqliteEventStore::new(pool);
+        inner.create_table().await.unwrap();
+        let keys_pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let key_store = SqliteKeyStore::new(keys_pool);
+        key_store.create_table().await.unwrap();
+        let store = EncryptedEventStore::new(inner.clone(), key_store);
+
+        let read_model = Arc::new(Mutex::new(HotelReadModel::default()));
+        let consumers =
+            EventConsumers::new().with(HotelProjectionConsumer::new(read_model.clone()));
+        let agg_manager = SimpleAggregateManager::new(store.clone());
+        let cqrs = SimpleCqrs::new(agg_manager, store.clone(), consumers);
+        let hotel_id = HotelId::new(1);
+
+        cqrs.execute(&hotel_id, &CmdInitializeHotel { room_count: 2 })
+            .await
+            .unwrap();
+        for (room_number, guest_name) in [(1, "Alice"), (2, "Bob")] {
+            cqrs.execute(
+                &hotel_id,
+                &CmdCheckIn {
+                    room_number,
+                    guest_name: guest_name.into(),
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        // Consumers receive the events as they were emitted.
+        let alice = RoomState::Occupied {
+            guest_name: "Alice".into(),
+        };
+        assert_eq!(read_model.lock().unwrap().rooms[&1], alice);
+
+        let aggregate_type = std::any::type_name::<HotelAggregate>();
+        let (stored, _) = inner
+            .load_events(aggregate_type, &hotel_id.to_string())
+            .await
+            .unwrap();
+        assert!(!stored[1].payload.to_string().contains("Alice"));
+
+        let checked_in: HotelEvent = store
+            .load_events(aggregate_type, &hotel_id.to_string())
+            .await
+            .unwrap()
+            .0[1]
+            .get_payload()
+            .unwrap();
+        let personal_data = checked_in.personal_data().unwrap();
+        assert_eq!(
+            personal_data,
+            PersonalData {
+                subject: "1-2".into(),
+                fields: vec!["/GuestCheckedIn/guest_name".into()],
+            }
+        );
+
+        assert!(store.forget_subject(&personal_data.subject).await.unwrap());
+        assert!(!store.forget_subject(&personal_data.subject).await.unwrap());
+
+        // The hotel still loads, with the name of the forgotten guest erased.
+        let hotel = SimpleAggregateManager::new(store.clone())
+            .load::<HotelAggregate>(&hotel_id)
+            .await
+            .unwrap();
+        let forgotten = RoomState::Occupied {
+            guest_name: String::new(),
+        };
+        assert_eq!(hotel.rooms[&1], forgotten);
+        let bob = RoomState::Occupied {
+            guest_name: "Bob".into(),
+        };
+        assert_eq!(hotel.rooms[&2], bob);
+
+        cqrs.execute(&hotel_id, &CmdCheckOut { room_number: 1 })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_event_store_scopes_tenants() {
+        use mini_cqrs_es::{EncryptedEventStore, EventStore, InMemoryKeyStore, TenantEventStore};
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let inner = SqliteEventStore::new(pool);
+        inner.create_table().await.unwrap();
+        let store = EncryptedEventStore::new(inner.clone(), InMemoryKeyStore::new());
+
+        let cqrs_for = |tenant_id: &str| {
+            let store = store.for_tenant(tenant_id);
+            assert_eq!(store.tenant_id(), Some(tenant_id));
+            let agg_manager = SimpleAggregateManager::new(store.clone());
+            SimpleCqrs::new(agg_manager, store, EventConsumers::new()).with_tenant(tenant_id)
+        };
+        let (acme, globex) = (cqrs_for("acme"), cqrs_for("globex"));
+        let acme_hotel = HotelId::new(1);
+
+        acme.execute(&acme_hotel, &CmdInitializeHotel { room_count: 2 })
+            .await
+            .unwrap();
+        let check_in = CmdCheckIn {
+            room_number: 1,
+            guest_name: "Alice".into(),
+        };
+        acme.execute(&acme_hotel, &check_in).await.unwrap();
+        let result = globex.execute(&acme_hotel, &check_in).await;
+        assert!(matches!(result, Err(CqrsError::TenantMismatch { .. })));
+
+        // The events are encrypted in the wrapped store, and decrypted by the scoped store.
+        let aggregate_type = std::any::type_name::<HotelAggregate>();
+        let (stored, _) = inner.load_events(aggregate_type, "1").await.unwrap();
+        assert_eq!(stored[1].metadata.tenant_id.as_deref(), Some("acme"));
+        assert!(!stored[1].payload.to_string().contains("Alice"));
+        let (events, _) = store
+            .for_tenant("acme")
+            .load_events(aggregate_type, "1")
+            .await
+            .unwrap();
+        assert!(events[1].payload.to_string().contains("Alice"));
+    }
 }
@@ -2,21 +2,91 @@
 
 // Common code shared in the examples to avoid repetitions and focus on the core concepts
 
-use std::{collections::HashMap, sync::Mutex};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
-use mini_cqrs_es::{CqrsError, EventStore, NewEvent, StoredEvent};
+use mini_cqrs_es::{
+    CqrsError, EventStore, ExpectedVersion, NewEvent, StoredEvent, TenantEventStore, UnitOfWork,
+};
 
-// Event Store
+type StreamKey = (String, String);
+
+// The global sequence of the last appended event of each tenant, across all streams.
+type Sequences = HashMap<Option<String>, i64>;
+
+// Event Store: clones, including the ones scoped to a tenant, share the same events.
+#[derive(Clone)]
 pub struct InMemoryEventStore {
-    events: Mutex<HashMap<String, Vec<StoredEvent>>>,
+    events: Arc<Mutex<HashMap<StreamKey, Vec<StoredEvent>>>>,
+    last_sequences: Arc<Mutex<Sequences>>,
+    tenant_id: Option<String>,
 }
 
 impl InMemoryEventStore {
     pub fn new() -> Self {
         InMemoryEventStore {
-            events: Mutex::new(HashMap::new()),
+            events: Arc::new(Mutex::new(HashMap::new())),
+            last_sequences: Arc::new(Mutex::new(HashMap::new())),
+            tenant_id: None,
+        }
+    }
+
+    // Checks that a stream belongs to the tenant of the store, streams being owned by the
+    // tenant of their first event.
+    fn check_stream(&self, aggregate_id: &str, events: &[StoredEvent]) -> Result<(), CqrsError> {
+        match events.first() {
+            Some(first) => self.check_tenant(aggregate_id, first.metadata.tenant_id.as_deref()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl TenantEventStore for InMemoryEventStore {
+    fn for_tenant(&self, tenant_id: &str) -> Self {
+        Self {
+            tenant_id: Some(tenant_id.to_string()),
+            ..self.clone()
         }
     }
+
+    fn tenant_id(&self) -> Option<&str> {
+        self.tenant_id.as_deref()
+    }
+}
+
+// Builds the envelopes for events appended to a stream currently at `actual_version`,
+// numbering them after the last global sequence of their tenant.
+fn stored_events(
+    aggregate_type: &str,
+    aggregate_id: &str,
+    events: &[NewEvent],
+    actual_version: u64,
+    last_sequences: &mut Sequences,
+) -> Vec<StoredEvent> {
+    events
+        .iter()
+        .enumerate()
+        .map(|(i, event)| {
+            let last_sequence = last_sequences
+                .entry(event.metadata.tenant_id.clone())
+                .or_default();
+            *last_sequence += 1;
+
+            StoredEvent {
+                id: event.id.clone(),
+                aggregate_id: aggregate_id.to_string(),
+                aggregate_type: aggregate_type.to_string(),
+                version: actual_version + i as u64 + 1,
+                event_type: event.event_type.clone(),
+                payload: event.payload.clone(),
+                metadata: event.metadata.clone(),
+                global_sequence: Some(*last_sequence),
+                timestamp: event.timestamp,
+            }
+        })
+        .collect()
 }
 
 impl EventStore for InMemoryEventStore {
@@ -25,54 +95,124 @@ impl EventStore for InMemoryEventStore {
         aggregate_type: &str,
         aggregate_id: &str,
         events: &[NewEvent],
-        expected_version: u64,
+        expected_version: ExpectedVersion,
     ) -> Result<Vec<StoredEvent>, CqrsError> {
+        let events = self.assign_tenant(aggregate_id, events)?;
         let mut store = self.events.lock().unwrap();
-        let current = store.entry(aggregate_id.to_string()).or_default();
+        let current = store
+            .entry((aggregate_type.to_string(), aggregate_id.to_string()))
+            .or_default();
+        self.check_stream(aggregate_id, current)?;
         let actual_version = current.last().map(|e| e.version).unwrap_or(0);
 
-        if actual_version != expected_version {
-            return Err(CqrsError::Conflict {
-                expected_version,
-                actual_version,
-            });
-        }
+        expected_version.check(aggregate_id, actual_version)?;
 
-        let mut persisted = Vec::with_capacity(events.len());
-        for (i, event) in events.iter().enumerate() {
-            let stored = StoredEvent {
-                id: format!("{aggregate_id}-{}", actual_version + i as u64 + 1),
-                aggregate_id: aggregate_id.to_string(),
-                aggregate_type: aggregate_type.to_string(),
-                version: actual_version + i as u64 + 1,
-                event_type: event.event_type.clone(),
-                payload: event.payload.clone(),
-                metadata: event.metadata.clone(),
-                global_sequence: None,
-                timestamp: event.timestamp,
-            };
-            persisted.push(stored);
+        let mut last_sequences = self.last_sequences.lock().unwrap();
+        let persisted = stored_events(
+            aggregate_type,
+            aggregate_id,
+            &events,
+            actual_version,
+            &mut last_sequences,
+        );
+        current.extend(persisted.clone());
+        Ok(persisted)
+    }
+
+    async fn save_unit_of_work(
+        &self,
+        unit: &UnitOfWork,
+    ) -> Result<Vec<Vec<StoredEvent>>, CqrsError> {
+        let mut store = self.events.lock().unwrap();
+        let mut last_sequences = self.last_sequences.lock().unwrap();
+        let mut next_sequences = last_sequences.clone();
+
+        // Stage every append first, so that nothing is written if any of them conflicts.
+        let mut staged: HashMap<StreamKey, Vec<StoredEvent>> = HashMap::new();
+        let mut persisted = Vec::with_capacity(unit.appends().len());
+
+        for append in unit.appends() {
+            let events = self.assign_tenant(&append.aggregate_id, &append.events)?;
+            let key = (append.aggregate_type.clone(), append.aggregate_id.clone());
+            let existing = store.get(&key).map(Vec::as_slice).unwrap_or_default();
+            self.check_stream(&append.aggregate_id, existing)?;
+
+            let actual_version = staged
+                .get(&key)
+                .and_then(|events| events.last())
+                .or_else(|| existing.last())
+                .map(|e| e.version)
+                .unwrap_or(0);
+
+            append
+                .expected_version
+                .check(&append.aggregate_id, actual_version)?;
+
+            let events = stored_events(
+                &append.aggregate_type,
+                &append.aggregate_id,
+                &events,
+                actual_version,
+                &mut next_sequences,
+            );
+            staged.entry(key).or_default().extend(events.clone());
+            persisted.push(events);
         }
 
-        if persisted.is_empty() {
-            return Ok(vec![]);
+        for (key, events) in staged {
+            store.entry(key).or_default().extend(events);
         }
+        *last_sequences = next_sequences;
 
-        current.extend(persisted.clone());
         Ok(persisted)
     }
 
     async fn load_events(
         &self,
-        _aggregate_type: &str,
+        aggregate_type: &str,
         aggregate_id: &str,
     ) -> Result<(Vec<StoredEvent>, u64), CqrsError> {
         let store = self.events.lock().unwrap();
-        if let Some(events) = store.get(aggregate_id) {
+        if let Some(events) = store.get(&(aggregate_type.to_string(), aggregate_id.to_string())) {
+            self.check_stream(aggregate_id, events)?;
             let version = events.last().map(|e| e.version).unwrap_or(0);
             Ok((events.to_vec(), version))
         } else {
             Ok((vec![], 0))
         }
     }
+
+    async fn delete_events_before(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+        version: u64,
+    ) -> Result<usize, CqrsError> {
+        let mut store = self.events.lock().unwrap();
+        let Some(events) = store.get_mut(&(aggregate_type.to_string(), aggregate_id.to_string()))
+        else {
+            return Ok(0);
+        };
+        self.check_stream(aggregate_id, events)?;
+
+        let count = events.len();
+        events.retain(|e| e.version >= version);
+        Ok(count - events.len())
+    }
+
+    async fn stream_ids(&self, aggregate_type: &str) -> Result<Vec<String>, CqrsError> {
+        let store = self.events.lock().unwrap();
+        let mut streams: Vec<_> = store
+            .iter()
+            .filter(|((stream_type, _), _)| stream_type == aggregate_type)
+            .filter(|((_, aggregate_id), events)| self.check_stream(aggregate_id, events).is_ok())
+            .filter_map(|((_, aggregate_id), events)| {
+                let first = events.first()?;
+                Some((first.global_sequence, aggregate_id.clone()))
+            })
+            .collect();
+        streams.sort();
+
+        Ok(streams.into_iter().map(|(_, aggregate_id)| aggregate_id).collect())
+    }
 }
@@ -12,8 +12,8 @@ use std::{
 use serde::{Deserialize, Serialize};
 
 use mini_cqrs_es::{
-    Aggregate, AggregateSnapshot, Command, CqrsError, EventConsumer, EventPayload, Query,
-    Repository, SnapshotStore, StoredEvent,
+    Aggregate, Command, CqrsError, EventConsumer, EventPayload, ExpectedVersion, Query,
+    Repository, StoredEvent,
 };
 
 #[path = "common.rs"]
@@ -43,62 +43,6 @@ impl FromStr for GameId {
     }
 }
 
-// Snapshot Store
-pub struct InMemorySnapshotStore<T>
-where
-    T: Aggregate,
-{
-    snapshots: Mutex<HashMap<String, AggregateSnapshot<T>>>,
-}
-
-impl<T> InMemorySnapshotStore<T>
-where
-    T: Aggregate,
-{
-    pub fn new() -> Self {
-        InMemorySnapshotStore {
-            snapshots: Mutex::new(HashMap::new()),
-        }
-    }
-}
-
-impl<A> SnapshotStore for InMemorySnapshotStore<A>
-where
-    A: Aggregate,
-{
-    async fn save_snapshot<T>(&self, snapshot: AggregateSnapshot<T>) -> Result<(), CqrsError>
-    where
-        T: Aggregate,
-    {
-        let aggregate = snapshot.get_payload::<A>()?;
-        let snapshot = AggregateSnapshot::new(&aggregate, Some(snapshot.version))?;
-        let aggregate_id = snapshot.aggregate_id.to_string();
-
-        let mut store = self.snapshots.lock().unwrap();
-        store.insert(aggregate_id, snapshot);
-        Ok(())
-    }
-
-    async fn load_snapshot<T>(
-        &self,
-        aggregate_id: &T::Id,
-    ) -> Result<AggregateSnapshot<T>, CqrsError>
-    where
-        T: Aggregate,
-    {
-        let store = self.snapshots.lock().unwrap();
-        if let Some(snapshot) = store.get(&aggregate_id.to_string()) {
-            let aggregate = snapshot.get_payload::<T>()?;
-            Ok(AggregateSnapshot::new(&aggregate, Some(snapshot.version))?)
-        } else {
-            Err(CqrsError::SnapshotStore(format!(
-                "No snapshot for aggregate id `{}`",
-                aggregate_id
-            )))
-        }
-    }
-}
-
 // Commands: for demonstration purposes, we can only start the game or attack the opponent.
 #[derive(PartialEq, Clone, Debug)]
 pub struct CmdStartGame {
@@ -124,6 +68,10 @@ impl Command for CmdStartGame {
             goal: self.goal,
         }])
     }
+
+    fn expected_version(&self) -> ExpectedVersion {
+        ExpectedVersion::NoStream
+    }
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -155,11 +103,15 @@ impl Command for CmdAttackPlayer {
 
         Ok(events)
     }
+
+    fn expected_version(&self) -> ExpectedVersion {
+        ExpectedVersion::StreamExists
+    }
 }
 
 // Events: the outcomes of the above commands, including the end of the game with a winner.
 // Note: aggregate_id is NOT needed in event variants — the framework handles it.
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, EventPayload)]
 pub enum GameEvent {
     GameStarted {
         player_1: Player,
@@ -174,18 +126,6 @@ pub enum GameEvent {
     },
 }
 
-impl fmt::Display for GameEvent {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            GameEvent::GameStarted { .. } => write!(f, "GameStarted"),
-            GameEvent::PlayerAttacked { .. } => write!(f, "PlayerAttacked"),
-            GameEvent::GameEndedWithWinner { .. } => write!(f, "GameEndedWithWinner"),
-        }
-    }
-}
-
-impl EventPayload for GameEvent {}
-
 // Aggregate: it's a more complex data structure with structs and enums as field values.
 #[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Player {
@@ -200,7 +140,8 @@ pub enum GameStatus {
 }
 
 // Game aggregate
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Aggregate)]
+#[aggregate(event = GameEvent)]
 pub struct GameAggregate {
     id: GameId,
     version: u64,
@@ -229,11 +170,8 @@ impl Default for GameAggregate {
     }
 }
 
-impl Aggregate for GameAggregate {
-    type Id = GameId;
-    type Event = GameEvent;
-
-    async fn apply(&mut self, event: &Self::Event) {
+impl GameAggregate {
+    async fn apply_event(&mut self, event: &GameEvent) -> Result<(), CqrsError> {
         match event {
             GameEvent::GameStarted {
                 player_1,
@@ -258,22 +196,7 @@ impl Aggregate for GameAggregate {
                 self.status = GameStatus::Winner(winner.clone());
             }
         };
-    }
-
-    fn aggregate_id(&self) -> Self::Id {
-        self.id.clone()
-    }
-
-    fn set_aggregate_id(&mut self, id: Self::Id) {
-        self.id = id;
-    }
-
-    fn version(&self) -> u64 {
-        self.version
-    }
-
-    fn set_version(&mut self, version: u64) {
-        self.version = version;
+        Ok(())
     }
 }
 
@@ -438,8 +361,19 @@ pub fn verify_game_result(
 
 #[cfg(test)]
 mod tests {
-    use super::GameId;
+    use super::{
+        CmdAttackPlayer, CmdStartGame, GameAggregate, GameEvent, GameId, InMemoryEventStore,
+        Player,
+    };
+    use mini_cqrs_es::{
+        AggregateManager, AggregateSnapshot, Cqrs, CqrsError, DivergenceKind, EventArchive,
+        EventConsumers, EventMetadata, EventStore, ExpectedVersion, InMemorySnapshotStore,
+        JsonlFileArchive, NewEvent, ReplayVerifier, SimpleAggregateManager, SimpleCqrs,
+        SnapshotAggregateManager, SnapshotStore, StreamArchiver, UnitOfWork,
+    };
+    use serde::{Deserialize, Serialize};
     use std::str::FromStr;
+    use std::sync::atomic::{AtomicU64, Ordering};
 
     #[test]
     fn test_game_id_display_fromstr_roundtrip() {
@@ -448,4 +382,335 @@ mod tests {
         let parsed = GameId::from_str(&rendered).unwrap();
         assert_eq!(parsed, id);
     }
+
+    #[tokio::test]
+    async fn test_in_memory_unit_of_work_is_all_or_nothing() {
+        let store = InMemoryEventStore::new();
+        let game_1 = GameId::new("game-1");
+        let game_2 = GameId::new("game-2");
+        let attack = NewEvent::from_payload(
+            GameEvent::PlayerAttacked {
+                attacker: Player::default(),
+            },
+            EventMetadata::default(),
+        )
+        .unwrap();
+
+        let unit = UnitOfWork::new()
+            .append_for::<GameAggregate>(&game_1, vec![attack.clone()], ExpectedVersion::NoStream)
+            .append_for::<GameAggregate>(&game_2, vec![attack.clone()], ExpectedVersion::Exact(3));
+
+        let result = store.save_unit_of_work(&unit).await;
+        assert!(matches!(result, Err(CqrsError::Conflict { .. })));
+
+        let (events, version) = store
+            .load_events(std::any::type_name::<GameAggregate>(), "game-1")
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+        assert_eq!(version, 0);
+
+        let unit = UnitOfWork::new()
+            .append_for::<GameAggregate>(&game_1, vec![attack.clone()], ExpectedVersion::NoStream)
+            .append_for::<GameAggregate>(&game_1, vec![attack], ExpectedVersion::Exact(1));
+
+        let persisted = store.save_unit_of_work(&unit).await.unwrap();
+        assert_eq!(persisted[0][0].version, 1);
+        assert_eq!(persisted[1][0].version, 2);
+    }
+
+    #[tokio::test]
+    async fn test_replay_verifier_compares_replays_with_snapshots() {
+        let store = InMemoryEventStore::new();
+        let snapshots = InMemorySnapshotStore::new();
+        let cqrs = SimpleCqrs::new(
+            SnapshotAggregateManager::new(snapshots.clone()).with_event_store(store.clone()),
+            store.clone(),
+            EventConsumers::new(),
+        );
+        let player_1 = Player {
+            id: "player_1".to_string(),
+            points: 0,
+        };
+        let player_2 = Player {
+            id: "player_2".to_string(),
+            points: 0,
+        };
+
+        for game in ["game-1", "game-2"] {
+            let game_id = GameId::new(game);
+            let start = CmdStartGame {
+                player_1: player_1.clone(),
+                player_2: player_2.clone(),
+                goal: 3,
+            };
+            cqrs.execute(&game_id, &start).await.unwrap();
+            let attack = CmdAttackPlayer {
+                attacker: player_1.clone(),
+            };
+            cqrs.execute(&game_id, &attack).await.unwrap();
+        }
+
+        let verifier = ReplayVerifier::new(store).with_snapshot_store(snapshots.clone());
+        let report = verifier.verify_all::<GameAggregate>().await.unwrap();
+        assert_eq!((report.streams, report.events), (2, 4));
+        assert!(report.is_consistent(), "{report:?}");
+
+        // A snapshot that doesn't match the events is reported at its version.
+        let game_id = GameId::new("game-1");
+        let snapshot = snapshots
+            .load_snapshot::<GameAggregate>(&game_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut tampered = snapshot.get_payload::<GameAggregate>().unwrap();
+        tampered.goal = 99;
+        snapshots
+            .save_snapshot(AggregateSnapshot::new(&tampered, Some(snapshot.version)).unwrap())
+            .await
+            .unwrap();
+
+        let divergences = verifier.verify::<GameAggregate>(&game_id).await.unwrap();
+        assert_eq!(divergences.len(), 1);
+        assert_eq!(divergences[0].kind, DivergenceKind::Snapshot);
+        assert_eq!(divergences[0].version, 2);
+        assert_eq!(divergences[0].expected["goal"], 3);
+        assert_eq!(divergences[0].actual["goal"], 99);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_manager_catches_up_and_discards_incompatible_snapshots() {
+        let store = InMemoryEventStore::new();
+        let snapshots = InMemorySnapshotStore::new();
+        let manager =
+            SnapshotAggregateManager::new(snapshots.clone()).with_event_store(store.clone());
+        let cqrs = SimpleCqrs::new(
+            SnapshotAggregateManager::new(snapshots.clone()).with_event_store(store.clone()),
+            store.clone(),
+            EventConsumers::new(),
+        );
+        let game_id = GameId::new("game-1");
+        let player_1 = Player {
+            id: "player_1".to_string(),
+            points: 0,
+        };
+        let start = CmdStartGame {
+            player_1: player_1.clone(),
+            player_2: Player {
+                id: "player_2".to_string(),
+                points: 0,
+            },
+            goal: 3,
+        };
+        cqrs.execute(&game_id, &start).await.unwrap();
+
+        // The events appended after the latest snapshot are replayed on top of it.
+        let attack = CmdAttackPlayer {
+            attacker: player_1.clone(),
+        };
+        let without_snapshots = SimpleCqrs::new(
+            SimpleAggregateManager::new(store.clone()),
+            store.clone(),
+            EventConsumers::new(),
+        );
+        without_snapshots.execute(&game_id, &attack).await.unwrap();
+        let game: GameAggregate = manager.load(&game_id).await.unwrap();
+        assert_eq!((game.version, game.player_1.points), (2, 1));
+
+        // A snapshot of another schema is discarded, even if it can't be deserialized.
+        let legacy = AggregateSnapshot::<GameAggregate>::from_stored(
+            game_id.clone(),
+            serde_json::json!({ "score": "1-0" }),
+            2,
+            0,
+        );
+        snapshots.save_snapshot(legacy).await.unwrap();
+        let game: GameAggregate = manager.load(&game_id).await.unwrap();
+        assert_eq!((game.version, game.player_1.points, game.goal), (2, 1, 3));
+
+        // The next command replaces it with a compatible snapshot.
+        cqrs.execute(&game_id, &attack).await.unwrap();
+        let snapshot = snapshots
+            .load_snapshot::<GameAggregate>(&game_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(snapshot.is_compatible());
+        assert_eq!(snapshot.version, 3);
+    }
+
+    // Stands for any global state read by `apply`, such as the system clock.
+    static APPLY_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    #[derive(Clone, Debug, Default, Serialize, Deserialize, mini_cqrs_es::Aggregate)]
+    #[aggregate(event = GameEvent)]
+    struct NondeterministicGame {
+        id: String,
+        version: u64,
+        applied_at: Vec<u64>,
+    }
+
+    impl NondeterministicGame {
+        async fn apply_event(&mut self, _event: &GameEvent) -> Result<(), CqrsError> {
+            self.applied_at
+                .push(APPLY_COUNTER.fetch_add(1, Ordering::Relaxed));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_verifier_reports_nondeterministic_apply() {
+        let store = InMemoryEventStore::new();
+        let game_id = "game-1".to_string();
+        let event = NewEvent::from_payload(
+            GameEvent::PlayerAttacked {
+                attacker: Player::default(),
+            },
+            EventMetadata::default(),
+        )
+        .unwrap();
+        store
+            .save_events(
+                std::any::type_name::<NondeterministicGame>(),
+                &game_id,
+                &[event],
+                ExpectedVersion::NoStream,
+            )
+            .await
+            .unwrap();
+
+        let verifier = ReplayVerifier::new(store);
+        let divergences = verifier
+            .verify::<NondeterministicGame>(&game_id)
+            .await
+            .unwrap();
+
+        assert_eq!(divergences.len(), 1);
+        assert_eq!(divergences[0].kind, DivergenceKind::Replay);
+        assert_eq!(divergences[0].aggregate_id, game_id);
+        assert_eq!(divergences[0].version, 1);
+        assert_ne!(divergences[0].expected, divergences[0].actual);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_snapshot_store_prunes_old_snapshots() {
+        let snapshots = InMemorySnapshotStore::new().with_retention(2);
+        let game_id = GameId::new("game-1");
+
+        for version in 1..=3 {
+            let game = GameAggregate {
+                id: game_id.clone(),
+                version,
+                ..GameAggregate::default()
+            };
+            snapshots
+                .save_snapshot(AggregateSnapshot::new(&game, Some(version)).unwrap())
+                .await
+                .unwrap();
+        }
+        // Another aggregate type with the same ID has its own snapshots.
+        let other = NondeterministicGame {
+            id: game_id.to_string(),
+            version: 7,
+            applied_at: vec![],
+        };
+        snapshots
+            .save_snapshot(AggregateSnapshot::new(&other, Some(7)).unwrap())
+            .await
+            .unwrap();
+
+        let versions = |history: Vec<AggregateSnapshot<GameAggregate>>| {
+            history.iter().map(|s| s.version).collect::<Vec<_>>()
+        };
+        assert_eq!(versions(snapshots.history(&game_id)), [3, 2]);
+        assert_eq!(
+            snapshots
+                .history::<NondeterministicGame>(&game_id.to_string())
+                .len(),
+            1
+        );
+
+        assert_eq!(snapshots.prune::<GameAggregate>(&game_id, 1), 1);
+        assert_eq!(versions(snapshots.history(&game_id)), [3]);
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_file_archive_keeps_archived_events_readable() {
+        let path = std::env::temp_dir().join(format!("archive-{}.jsonl", uuid::Uuid::now_v7()));
+        let store = InMemoryEventStore::new();
+        let snapshots = InMemorySnapshotStore::new();
+        let cqrs = SimpleCqrs::new(
+            SnapshotAggregateManager::new(snapshots.clone()).with_event_store(store.clone()),
+            store.clone(),
+            EventConsumers::new(),
+        );
+        let game_id = GameId::new("game-1");
+        let player_1 = Player {
+            id: "player_1".to_string(),
+            points: 0,
+        };
+        let start = CmdStartGame {
+            player_1: player_1.clone(),
+            player_2: Player {
+                id: "player_2".to_string(),
+                points: 0,
+            },
+            goal: 5,
+        };
+        cqrs.execute(&game_id, &start).await.unwrap();
+        for _ in 0..2 {
+            let attack = CmdAttackPlayer {
+                attacker: player_1.clone(),
+            };
+            cqrs.execute(&game_id, &attack).await.unwrap();
+        }
+
+        let archive = JsonlFileArchive::new(&path);
+        let archiver = StreamArchiver::new(store.clone(), snapshots, archive);
+        assert_eq!(archiver.archive::<GameAggregate>(&game_id).await.unwrap(), 2);
+        assert_eq!(archiver.archive::<GameAggregate>(&game_id).await.unwrap(), 0);
+
+        // Archiving the same events again doesn't duplicate them.
+        let archive = JsonlFileArchive::new(&path);
+        let archived = archive
+            .load_archived_events(std::any::type_name::<GameAggregate>(), "game-1")
+            .await
+            .unwrap();
+        archive.archive_events(&archived).await.unwrap();
+
+        let history = archiver
+            .load_history::<GameAggregate>(&game_id)
+            .await
+            .unwrap();
+        let versions: Vec<_> = history.iter().map(|e| e.version).collect();
+        assert_eq!(versions, [1, 2, 3]);
+        assert_eq!(archive.load_archived_events_after(1, 10).await.unwrap().len(), 1);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_event_store_lists_streams() {
+        mini_cqrs_es::testkit::event_store::check_stream_listing(&InMemoryEventStore::new())
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_event_store_truncates_streams() {
+        mini_cqrs_es::testkit::event_store::check_truncation(&InMemoryEventStore::new()).await;
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_event_store_isolates_tenants() {
+        mini_cqrs_es::testkit::event_store::check_tenant_isolation(&InMemoryEventStore::new())
+            .await;
+    }
+
+    mini_cqrs_es::event_store_conformance!(in_memory_event_store, async {
+        InMemoryEventStore::new()
+    });
+
+    mini_cqrs_es::snapshot_store_conformance!(in_memory_snapshot_store, async {
+        InMemorySnapshotStore::new()
+    });
 }
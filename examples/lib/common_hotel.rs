@@ -1,7 +1,7 @@
 #![allow(dead_code)]
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt,
     str::FromStr,
     sync::{Arc, Mutex},
@@ -10,7 +10,8 @@ use std::{
 use serde::{Deserialize, Serialize};
 
 use mini_cqrs_es::{
-    Aggregate, Command, CqrsError, EventConsumer, EventPayload, Query, StoredEvent,
+    Aggregate, Command, CqrsError, EventConsumer, EventPayload, ExpectedVersion, Query,
+    StoredEvent,
 };
 
 #[path = "sqlite_store.rs"]
@@ -49,31 +50,29 @@ pub enum RoomState {
 
 // --- Events ---
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, EventPayload)]
 pub enum HotelEvent {
     HotelInitialized { room_count: u32 },
-    GuestCheckedIn { room_number: u32, guest_name: String },
+    GuestCheckedIn {
+        room_number: u32,
+        // Identifies the stay of the guest, whose name is erased once the stay is forgotten.
+        #[event(subject)]
+        stay_id: String,
+        #[event(personal_data)]
+        #[serde(default)]
+        guest_name: String,
+    },
     GuestCheckedOut { room_number: u32 },
 }
 
-impl fmt::Display for HotelEvent {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            HotelEvent::HotelInitialized { .. } => write!(f, "HotelInitialized"),
-            HotelEvent::GuestCheckedIn { .. } => write!(f, "GuestCheckedIn"),
-            HotelEvent::GuestCheckedOut { .. } => write!(f, "GuestCheckedOut"),
-        }
-    }
-}
-
-impl EventPayload for HotelEvent {}
-
 // --- Aggregate ---
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Aggregate)]
+#[aggregate(event = HotelEvent, invariants = check_guests)]
 pub struct HotelAggregate {
     id: HotelId,
     version: u64,
+    pub room_count: u32,
     pub rooms: HashMap<u32, RoomState>,
 }
 
@@ -82,26 +81,29 @@ impl Default for HotelAggregate {
         Self {
             id: HotelId::new(0),
             version: 0,
+            room_count: 0,
             rooms: HashMap::new(),
         }
     }
 }
 
-impl Aggregate for HotelAggregate {
-    type Id = HotelId;
-    type Event = HotelEvent;
-
-    async fn apply(&mut self, event: &Self::Event) {
+impl HotelAggregate {
+    async fn apply_event(&mut self, event: &HotelEvent) -> Result<(), CqrsError> {
         match event {
             HotelEvent::HotelInitialized { room_count } => {
+                self.room_count = *room_count;
                 self.rooms.clear();
                 for i in 1..=*room_count {
                     self.rooms.insert(i, RoomState::Free);
                 }
             }
+            HotelEvent::GuestCheckedOut { room_number } if !self.rooms.contains_key(room_number) => {
+                return Err(CqrsError::domain(format!("Room {room_number} does not exist")));
+            }
             HotelEvent::GuestCheckedIn {
                 room_number,
                 guest_name,
+                ..
             } => {
                 self.rooms.insert(
                     *room_number,
@@ -114,27 +116,30 @@ impl Aggregate for HotelAggregate {
                 self.rooms.insert(*room_number, RoomState::Free);
             }
         }
+        Ok(())
     }
 
-    fn aggregate_id(&self) -> Self::Id {
-        self.id.clone()
-    }
-
-    fn set_aggregate_id(&mut self, id: Self::Id) {
-        self.id = id;
-    }
-
-    fn version(&self) -> u64 {
-        self.version
-    }
-
-    fn set_version(&mut self, version: u64) {
-        self.version = version;
+    // A guest stays in a single room at a time. Names erased when stays are forgotten are
+    // ignored.
+    fn check_guests(&self) -> Result<(), CqrsError> {
+        let mut guests = HashSet::new();
+        for room in self.rooms.values() {
+            if let RoomState::Occupied { guest_name } = room
+                && !guest_name.is_empty()
+                && !guests.insert(guest_name)
+            {
+                return Err(CqrsError::invariant(format!(
+                    "{guest_name} already occupies another room"
+                )));
+            }
+        }
+        Ok(())
     }
 }
 
 // --- Commands ---
 
+#[derive(Deserialize)]
 pub struct CmdInitializeHotel {
     pub room_count: u32,
 }
@@ -150,8 +155,13 @@ impl Command for CmdInitializeHotel {
             room_count: self.room_count,
         }])
     }
+
+    fn expected_version(&self) -> ExpectedVersion {
+        ExpectedVersion::NoStream
+    }
 }
 
+#[derive(Deserialize)]
 pub struct CmdCheckIn {
     pub room_number: u32,
     pub guest_name: String,
@@ -168,6 +178,7 @@ impl Command for CmdCheckIn {
             ))),
             Some(RoomState::Free) => Ok(vec![HotelEvent::GuestCheckedIn {
                 room_number: self.room_number,
+                stay_id: format!("{}-{}", aggregate.aggregate_id(), aggregate.version() + 1),
                 guest_name: self.guest_name.clone(),
             }]),
             None => Err(CqrsError::domain(format!(
@@ -176,8 +187,13 @@ impl Command for CmdCheckIn {
             ))),
         }
     }
+
+    fn expected_version(&self) -> ExpectedVersion {
+        ExpectedVersion::StreamExists
+    }
 }
 
+#[derive(Deserialize)]
 pub struct CmdCheckOut {
     pub room_number: u32,
 }
@@ -200,6 +216,10 @@ impl Command for CmdCheckOut {
             ))),
         }
     }
+
+    fn expected_version(&self) -> ExpectedVersion {
+        ExpectedVersion::StreamExists
+    }
 }
 
 // --- Read Model ---
@@ -237,6 +257,7 @@ impl EventConsumer for HotelProjectionConsumer {
             HotelEvent::GuestCheckedIn {
                 room_number,
                 guest_name,
+                ..
             } => {
                 model
                     .rooms
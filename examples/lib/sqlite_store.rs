@@ -1,40 +1,143 @@
 #![allow(dead_code)]
 
-use chrono::Utc;
-use sqlx::SqlitePool;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::{Sqlite, SqliteExecutor, SqlitePool, Transaction};
 
-use mini_cqrs_es::{CqrsError, EventMetadata, EventStore, NewEvent, StoredEvent};
+use mini_cqrs_es::{
+    CqrsError, EventMetadata, EventStore, ExpectedVersion, NewEvent, OutboxEntry, OutboxStore,
+    StoredEvent, TenantEventStore, UnitOfWork,
+};
+
+type EventRow = (String, String, String, String, String, String, i64, i64, String);
+
+type OutboxRow = (
+    i64,
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    i64,
+    i64,
+    String,
+);
 
 /// An event store backed by SQLite via sqlx.
+///
+/// Events appended without a tenant are stored with an empty `tenant_id`, and global
+/// sequences are numbered per tenant.
 #[derive(Clone)]
 pub struct SqliteEventStore {
     pool: SqlitePool,
+    outbox: bool,
+    tenant_id: Option<String>,
 }
 
 impl SqliteEventStore {
     pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            outbox: false,
+            tenant_id: None,
+        }
+    }
+
+    /// Records every appended event in the `outbox` table, in the same transaction.
+    pub fn with_outbox(mut self) -> Self {
+        self.outbox = true;
+        self
     }
 
+    /// Creates the `events` and `outbox` tables if they don't exist.
+    ///
+    /// Tables created before streams were scoped to tenants, whose `global_sequence` column
+    /// was the primary key of `events`, are migrated first: events are given the tenant named
+    /// by their metadata (an empty one if none) and renumbered per tenant, keeping their order,
+    /// and outbox entries reference events by ID instead of global sequence.
     pub async fn create_table(&self) -> Result<(), sqlx::Error> {
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS events (
-                id TEXT NOT NULL,
-                aggregate_type TEXT NOT NULL,
-                event_type TEXT NOT NULL,
-                aggregate_id TEXT NOT NULL,
-                payload TEXT NOT NULL,
-                metadata TEXT NOT NULL,
-                version INTEGER NOT NULL,
-                global_sequence INTEGER PRIMARY KEY AUTOINCREMENT,
-                timestamp TEXT NOT NULL,
-                UNIQUE (aggregate_type, aggregate_id, version)
-            )",
-        )
+        self.migrate_legacy_tables().await?;
+
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS events ({EVENTS_COLUMNS})"
+        ))
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS outbox ({OUTBOX_COLUMNS})"
+        ))
         .execute(&self.pool)
         .await?;
         Ok(())
     }
+
+    // Rebuilds the tables of a database created before tenants, following the SQLite procedure
+    // for schema changes: foreign keys are disabled on the connection while tables are
+    // replaced, in a transaction.
+    async fn migrate_legacy_tables(&self) -> Result<(), sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+
+        let columns: Vec<(String,)> =
+            sqlx::query_as("SELECT name FROM pragma_table_info('events')")
+                .fetch_all(&mut *conn)
+                .await?;
+        if columns.is_empty() || columns.iter().any(|(name,)| name == "tenant_id") {
+            return Ok(());
+        }
+
+        sqlx::query("PRAGMA foreign_keys = OFF")
+            .execute(&mut *conn)
+            .await?;
+        let migrated = rebuild_legacy_tables(&mut conn).await;
+        sqlx::query("PRAGMA foreign_keys = ON")
+            .execute(&mut *conn)
+            .await?;
+        migrated
+    }
+
+    // Checks that a stream belongs to the tenant of the store, when it is scoped to one.
+    async fn check_stream<'e, E>(
+        &self,
+        executor: E,
+        aggregate_type: &str,
+        aggregate_id: &str,
+    ) -> Result<(), CqrsError>
+    where
+        E: SqliteExecutor<'e>,
+    {
+        if self.tenant_id.is_none() {
+            return Ok(());
+        }
+
+        let owner: Option<(String,)> = sqlx::query_as(
+            "SELECT tenant_id FROM events WHERE aggregate_type = ? AND aggregate_id = ? LIMIT 1",
+        )
+        .bind(aggregate_type)
+        .bind(aggregate_id)
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+
+        match owner {
+            Some((owner,)) => {
+                self.check_tenant(aggregate_id, Some(owner.as_str()).filter(|o| !o.is_empty()))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl TenantEventStore for SqliteEventStore {
+    fn for_tenant(&self, tenant_id: &str) -> Self {
+        Self {
+            tenant_id: Some(tenant_id.to_string()),
+            ..self.clone()
+        }
+    }
+
+    fn tenant_id(&self) -> Option<&str> {
+        self.tenant_id.as_deref()
+    }
 }
 
 impl EventStore for SqliteEventStore {
@@ -43,7 +146,7 @@ impl EventStore for SqliteEventStore {
         aggregate_type: &str,
         aggregate_id: &str,
         events: &[NewEvent],
-        expected_version: u64,
+        expected_version: ExpectedVersion,
     ) -> Result<Vec<StoredEvent>, CqrsError> {
         let mut tx = self
             .pool
@@ -51,127 +154,523 @@ impl EventStore for SqliteEventStore {
             .await
             .map_err(|e| CqrsError::EventStore(e.to_string()))?;
 
-        // Check optimistic concurrency
+        let persisted = append_events(
+            &mut tx,
+            self,
+            aggregate_type,
+            aggregate_id,
+            events,
+            expected_version,
+        )
+        .await?;
+
+        tx.commit()
+            .await
+            .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+        Ok(persisted)
+    }
+
+    async fn save_unit_of_work(
+        &self,
+        unit: &UnitOfWork,
+    ) -> Result<Vec<Vec<StoredEvent>>, CqrsError> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+
+        // Any failure drops the transaction, rolling back the streams appended so far.
+        let mut persisted = Vec::with_capacity(unit.appends().len());
+        for append in unit.appends() {
+            persisted.push(
+                append_events(
+                    &mut tx,
+                    self,
+                    &append.aggregate_type,
+                    &append.aggregate_id,
+                    &append.events,
+                    append.expected_version,
+                )
+                .await?,
+            );
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+        Ok(persisted)
+    }
+
+    async fn load_events(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+    ) -> Result<(Vec<StoredEvent>, u64), CqrsError> {
+        self.check_stream(&self.pool, aggregate_type, aggregate_id)
+            .await?;
+
+        let rows: Vec<EventRow> = sqlx::query_as(
+            "SELECT id, aggregate_type, event_type, aggregate_id, payload, metadata, version, global_sequence, timestamp
+             FROM events
+             WHERE aggregate_type = ? AND aggregate_id = ?
+             ORDER BY version ASC",
+        )
+        .bind(aggregate_type)
+        .bind(aggregate_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+
+        rows_to_events(rows)
+    }
+
+    async fn stream_version(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+    ) -> Result<u64, CqrsError> {
+        self.check_stream(&self.pool, aggregate_type, aggregate_id)
+            .await?;
+
         let row: (i64,) = sqlx::query_as(
             "SELECT COALESCE(MAX(version), 0) FROM events WHERE aggregate_type = ? AND aggregate_id = ?",
         )
         .bind(aggregate_type)
         .bind(aggregate_id)
-        .fetch_one(&mut *tx)
+        .fetch_one(&self.pool)
         .await
         .map_err(|e| CqrsError::EventStore(e.to_string()))?;
 
-        let actual_version = row.0 as u64;
-        if actual_version != expected_version {
-            return Err(CqrsError::Conflict {
-                expected_version,
-                actual_version,
-            });
-        }
+        Ok(row.0 as u64)
+    }
 
-        let mut persisted = Vec::with_capacity(events.len());
-        for (i, event) in events.iter().enumerate() {
-            let payload_json =
-                serde_json::to_string(&event.payload).map_err(|e| CqrsError::EventStore(e.to_string()))?;
-            let metadata_json = serde_json::to_string(&event.metadata)
-                .map_err(|e| CqrsError::EventStore(e.to_string()))?;
-            let version = actual_version + i as u64 + 1;
-            let id = format!("{aggregate_type}-{aggregate_id}-{version}");
-
-            let seq: i64 = sqlx::query_scalar(
-                "INSERT INTO events (id, aggregate_type, event_type, aggregate_id, payload, metadata, version, timestamp)
-                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)
-                 RETURNING global_sequence",
-            )
-            .bind(&id)
-            .bind(aggregate_type)
-            .bind(&event.event_type)
-            .bind(aggregate_id)
-            .bind(&payload_json)
-            .bind(&metadata_json)
-            .bind(version as i64)
-            .bind(event.timestamp.to_rfc3339())
-            .fetch_one(&mut *tx)
+    async fn delete_events_before(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+        version: u64,
+    ) -> Result<usize, CqrsError> {
+        let mut tx = self
+            .pool
+            .begin()
             .await
             .map_err(|e| CqrsError::EventStore(e.to_string()))?;
 
-            persisted.push(StoredEvent {
-                id,
-                aggregate_id: aggregate_id.to_string(),
-                aggregate_type: aggregate_type.to_string(),
-                version,
-                event_type: event.event_type.clone(),
-                payload: event.payload.clone(),
-                metadata: event.metadata.clone(),
-                global_sequence: Some(seq),
-                timestamp: event.timestamp,
-            });
+        self.check_stream(&mut *tx, aggregate_type, aggregate_id)
+            .await?;
+
+        // Events still waiting in the outbox must be published before they are deleted, and
+        // the outbox entries of the delivered ones go with them.
+        let pending: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM outbox o
+             JOIN events e ON e.id = o.event_id
+             WHERE e.aggregate_type = ? AND e.aggregate_id = ? AND e.version < ?
+                 AND o.delivered_at IS NULL",
+        )
+        .bind(aggregate_type)
+        .bind(aggregate_id)
+        .bind(version as i64)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+        if pending.0 > 0 {
+            return Err(CqrsError::EventStore(format!(
+                "{} events of {aggregate_type} {aggregate_id} are still pending in the outbox",
+                pending.0
+            )));
         }
 
+        sqlx::query(
+            "DELETE FROM outbox WHERE event_id IN (
+                 SELECT id FROM events WHERE aggregate_type = ? AND aggregate_id = ? AND version < ?
+             )",
+        )
+        .bind(aggregate_type)
+        .bind(aggregate_id)
+        .bind(version as i64)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+
+        let result = sqlx::query(
+            "DELETE FROM events WHERE aggregate_type = ? AND aggregate_id = ? AND version < ?",
+        )
+        .bind(aggregate_type)
+        .bind(aggregate_id)
+        .bind(version as i64)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+
         tx.commit()
             .await
             .map_err(|e| CqrsError::EventStore(e.to_string()))?;
-        Ok(persisted)
+        Ok(result.rows_affected() as usize)
     }
 
-    async fn load_events(
+    async fn stream_ids(&self, aggregate_type: &str) -> Result<Vec<String>, CqrsError> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "SELECT aggregate_id FROM events WHERE aggregate_type = ? AND (? IS NULL OR tenant_id = ?)
+             GROUP BY aggregate_id ORDER BY MIN(position)",
+        )
+        .bind(aggregate_type)
+        .bind(&self.tenant_id)
+        .bind(&self.tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+
+        Ok(rows.into_iter().map(|(aggregate_id,)| aggregate_id).collect())
+    }
+
+    async fn load_events_to_version(
         &self,
         aggregate_type: &str,
         aggregate_id: &str,
+        version: u64,
     ) -> Result<(Vec<StoredEvent>, u64), CqrsError> {
-        let rows: Vec<(String, String, String, String, String, String, i64, i64, String)> =
-            sqlx::query_as(
-                "SELECT id, aggregate_type, event_type, aggregate_id, payload, metadata, version, global_sequence, timestamp
+        self.check_stream(&self.pool, aggregate_type, aggregate_id)
+            .await?;
+
+        let rows: Vec<EventRow> = sqlx::query_as(
+            "SELECT id, aggregate_type, event_type, aggregate_id, payload, metadata, version, global_sequence, timestamp
              FROM events
-             WHERE aggregate_type = ? AND aggregate_id = ?
+             WHERE aggregate_type = ? AND aggregate_id = ? AND version <= ?
              ORDER BY version ASC",
-            )
-            .bind(aggregate_type)
-            .bind(aggregate_id)
-            .fetch_all(&self.pool)
-            .await
-            .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+        )
+        .bind(aggregate_type)
+        .bind(aggregate_id)
+        .bind(version as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+
+        rows_to_events(rows)
+    }
+
+    async fn load_events_after(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+        version: u64,
+    ) -> Result<(Vec<StoredEvent>, u64), CqrsError> {
+        self.check_stream(&self.pool, aggregate_type, aggregate_id)
+            .await?;
+
+        let rows: Vec<EventRow> = sqlx::query_as(
+            "SELECT id, aggregate_type, event_type, aggregate_id, payload, metadata, version, global_sequence, timestamp
+             FROM events
+             WHERE aggregate_type = ? AND aggregate_id = ? AND version > ?
+             ORDER BY version ASC",
+        )
+        .bind(aggregate_type)
+        .bind(aggregate_id)
+        .bind(version as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+
+        if rows.is_empty() {
+            let current_version = self.stream_version(aggregate_type, aggregate_id).await?;
+            return Ok((vec![], current_version));
+        }
+        rows_to_events(rows)
+    }
 
-        let mut events = Vec::with_capacity(rows.len());
-        let mut max_version: u64 = 0;
+    async fn load_events_as_of(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(Vec<StoredEvent>, u64), CqrsError> {
+        self.check_stream(&self.pool, aggregate_type, aggregate_id)
+            .await?;
 
+        let rows: Vec<EventRow> = sqlx::query_as(
+            "SELECT id, aggregate_type, event_type, aggregate_id, payload, metadata, version, global_sequence, timestamp
+             FROM events
+             WHERE aggregate_type = ? AND aggregate_id = ? AND timestamp <= ?
+             ORDER BY version ASC",
+        )
+        .bind(aggregate_type)
+        .bind(aggregate_id)
+        .bind(format_timestamp(&timestamp))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+
+        rows_to_events(rows)
+    }
+}
+
+impl OutboxStore for SqliteEventStore {
+    async fn pending(&self, limit: usize) -> Result<Vec<OutboxEntry>, CqrsError> {
+        let rows: Vec<OutboxRow> = sqlx::query_as(
+            "SELECT o.id, e.id, e.aggregate_type, e.event_type, e.aggregate_id, e.payload, e.metadata, e.version, e.global_sequence, e.timestamp
+             FROM outbox o
+             JOIN events e ON e.id = o.event_id
+             WHERE o.delivered_at IS NULL
+             ORDER BY o.id ASC
+             LIMIT ?",
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+
+        let mut entries = Vec::with_capacity(rows.len());
         for (
+            outbox_id,
             id,
-            row_aggregate_type,
+            aggregate_type,
             event_type,
-            row_aggregate_id,
-            payload_str,
-            metadata_str,
+            aggregate_id,
+            payload,
+            metadata,
             version,
             global_sequence,
-            timestamp_str,
+            timestamp,
         ) in rows
         {
-            let version = version as u64;
-            if version > max_version {
-                max_version = version;
-            }
-
-            let payload_value: serde_json::Value = serde_json::from_str(&payload_str)
-                .map_err(|e| CqrsError::EventStore(format!("Failed to parse payload JSON: {}", e)))?;
-            let metadata: EventMetadata = serde_json::from_str(&metadata_str)
-                .map_err(|e| CqrsError::EventStore(format!("Failed to parse metadata JSON: {}", e)))?;
-
-            events.push(StoredEvent {
-                id,
-                aggregate_id: row_aggregate_id,
-                aggregate_type: row_aggregate_type,
-                version,
-                event_type,
-                payload: payload_value,
-                metadata,
-                global_sequence: Some(global_sequence),
-                timestamp: chrono::DateTime::parse_from_rfc3339(&timestamp_str)
-                    .map(|dt| dt.with_timezone(&Utc))
-                    .unwrap_or_else(|_| Utc::now()),
+            entries.push(OutboxEntry {
+                id: outbox_id,
+                event: row_to_event((
+                    id,
+                    aggregate_type,
+                    event_type,
+                    aggregate_id,
+                    payload,
+                    metadata,
+                    version,
+                    global_sequence,
+                    timestamp,
+                ))?,
             });
         }
 
-        Ok((events, max_version))
+        Ok(entries)
+    }
+
+    async fn mark_delivered(&self, ids: &[i64]) -> Result<(), CqrsError> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+
+        let delivered_at = format_timestamp(&Utc::now());
+        for id in ids {
+            sqlx::query("UPDATE outbox SET delivered_at = ? WHERE id = ?")
+                .bind(&delivered_at)
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+        Ok(())
+    }
+}
+
+// Appends events to a stream within an open transaction, checking the tenant and the expected
+// version first. When the store has an outbox, each event is also recorded in it.
+async fn append_events(
+    tx: &mut Transaction<'_, Sqlite>,
+    store: &SqliteEventStore,
+    aggregate_type: &str,
+    aggregate_id: &str,
+    events: &[NewEvent],
+    expected_version: ExpectedVersion,
+) -> Result<Vec<StoredEvent>, CqrsError> {
+    let events = store.assign_tenant(aggregate_id, events)?;
+    store
+        .check_stream(&mut **tx, aggregate_type, aggregate_id)
+        .await?;
+
+    // Check optimistic concurrency
+    let row: (i64,) = sqlx::query_as(
+        "SELECT COALESCE(MAX(version), 0) FROM events WHERE aggregate_type = ? AND aggregate_id = ?",
+    )
+    .bind(aggregate_type)
+    .bind(aggregate_id)
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+
+    let actual_version = row.0 as u64;
+    expected_version.check(aggregate_id, actual_version)?;
+
+    let mut persisted = Vec::with_capacity(events.len());
+    for (i, event) in events.iter().enumerate() {
+        let payload_json = serde_json::to_string(&event.payload)
+            .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+        let metadata_json = serde_json::to_string(&event.metadata)
+            .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+        let version = actual_version + i as u64 + 1;
+        let id = event.id.clone();
+        let tenant_id = event.metadata.tenant_id.as_deref().unwrap_or_default();
+
+        // The sequence is computed by the insert itself, which holds the write lock of the
+        // database, so that concurrent appends can't pick the same one.
+        let seq: i64 = sqlx::query_scalar(
+            "INSERT INTO events (id, aggregate_type, event_type, aggregate_id, payload, metadata, version, timestamp, tenant_id, global_sequence)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?,
+                 (SELECT COALESCE(MAX(global_sequence), 0) + 1 FROM events WHERE tenant_id = ?))
+             RETURNING global_sequence",
+        )
+        .bind(&id)
+        .bind(aggregate_type)
+        .bind(&event.event_type)
+        .bind(aggregate_id)
+        .bind(&payload_json)
+        .bind(&metadata_json)
+        .bind(version as i64)
+        .bind(format_timestamp(&event.timestamp))
+        .bind(tenant_id)
+        .bind(tenant_id)
+        .fetch_one(&mut **tx)
+        .await
+        .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+
+        if store.outbox {
+            sqlx::query("INSERT INTO outbox (event_id) VALUES (?)")
+                .bind(&id)
+                .execute(&mut **tx)
+                .await
+                .map_err(|e| CqrsError::EventStore(e.to_string()))?;
+        }
+
+        persisted.push(StoredEvent {
+            id,
+            aggregate_id: aggregate_id.to_string(),
+            aggregate_type: aggregate_type.to_string(),
+            version,
+            event_type: event.event_type.clone(),
+            payload: event.payload.clone(),
+            metadata: event.metadata.clone(),
+            global_sequence: Some(seq),
+            timestamp: event.timestamp,
+        });
     }
+
+    Ok(persisted)
+}
+
+// Timestamps are stored with a fixed-width format so that they can be compared as text.
+const EVENTS_COLUMNS: &str = "
+    id TEXT NOT NULL UNIQUE,
+    aggregate_type TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    aggregate_id TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    metadata TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    position INTEGER PRIMARY KEY AUTOINCREMENT,
+    tenant_id TEXT NOT NULL,
+    global_sequence INTEGER NOT NULL,
+    timestamp TEXT NOT NULL,
+    UNIQUE (aggregate_type, aggregate_id, version),
+    UNIQUE (tenant_id, global_sequence)";
+
+const OUTBOX_COLUMNS: &str = "
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    event_id TEXT NOT NULL REFERENCES events (id),
+    delivered_at TEXT";
+
+async fn rebuild_legacy_tables(conn: &mut sqlx::SqliteConnection) -> Result<(), sqlx::Error> {
+    let mut tx = sqlx::Connection::begin(conn).await?;
+
+    sqlx::query(&format!("CREATE TABLE events_migrated ({EVENTS_COLUMNS})"))
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO events_migrated (id, aggregate_type, event_type, aggregate_id, payload, metadata, version, position, tenant_id, global_sequence, timestamp)
+         SELECT id, aggregate_type, event_type, aggregate_id, payload, metadata, version, global_sequence, tenant_id,
+             ROW_NUMBER() OVER (PARTITION BY tenant_id ORDER BY global_sequence), timestamp
+         FROM (SELECT *, COALESCE(json_extract(metadata, '$.tenant_id'), '') AS tenant_id FROM events)",
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DROP TABLE events").execute(&mut *tx).await?;
+    sqlx::query("ALTER TABLE events_migrated RENAME TO events")
+        .execute(&mut *tx)
+        .await?;
+
+    let outbox: Vec<(String,)> = sqlx::query_as("SELECT name FROM pragma_table_info('outbox')")
+        .fetch_all(&mut *tx)
+        .await?;
+    if outbox.iter().any(|(name,)| name == "global_sequence") {
+        sqlx::query(&format!("CREATE TABLE outbox_migrated ({OUTBOX_COLUMNS})"))
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO outbox_migrated (id, event_id, delivered_at)
+             SELECT outbox.id, events.id, outbox.delivered_at
+             FROM outbox JOIN events ON events.position = outbox.global_sequence",
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DROP TABLE outbox").execute(&mut *tx).await?;
+        sqlx::query("ALTER TABLE outbox_migrated RENAME TO outbox")
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await
+}
+
+fn format_timestamp(timestamp: &DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+fn rows_to_events(rows: Vec<EventRow>) -> Result<(Vec<StoredEvent>, u64), CqrsError> {
+    let mut events = Vec::with_capacity(rows.len());
+    let mut max_version: u64 = 0;
+
+    for row in rows {
+        let event = row_to_event(row)?;
+        if event.version > max_version {
+            max_version = event.version;
+        }
+        events.push(event);
+    }
+
+    Ok((events, max_version))
+}
+
+fn row_to_event(
+    (
+        id,
+        row_aggregate_type,
+        event_type,
+        row_aggregate_id,
+        payload_str,
+        metadata_str,
+        version,
+        global_sequence,
+        timestamp_str,
+    ): EventRow,
+) -> Result<StoredEvent, CqrsError> {
+    let payload_value: serde_json::Value = serde_json::from_str(&payload_str)
+        .map_err(|e| CqrsError::EventStore(format!("Failed to parse payload JSON: {}", e)))?;
+    let metadata: EventMetadata = serde_json::from_str(&metadata_str)
+        .map_err(|e| CqrsError::EventStore(format!("Failed to parse metadata JSON: {}", e)))?;
+
+    Ok(StoredEvent {
+        id,
+        aggregate_id: row_aggregate_id,
+        aggregate_type: row_aggregate_type,
+        version: version as u64,
+        event_type,
+        payload: payload_value,
+        metadata,
+        global_sequence: Some(global_sequence),
+        timestamp: DateTime::parse_from_rfc3339(&timestamp_str)
+            .map(|dt| dt.with_timezone(&Utc))
+            .map_err(|e| CqrsError::EventStore(format!("Failed to parse timestamp: {}", e)))?,
+    })
 }
@@ -0,0 +1,389 @@
+//! Derive macros for [`mini_cqrs_es`](https://docs.rs/mini_cqrs_es).
+//!
+//! Enable the `derive` feature of `mini_cqrs_es` to use them, rather than depending on this
+//! crate directly:
+//!
+//! ```toml
+//! mini_cqrs_es = { version = "0.11", features = ["derive"] }
+//! ```
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Expr, Fields, Ident, Type};
+
+/// Derives `Display` and `EventPayload` for an event type.
+///
+/// Enums are displayed as the name of their variant, which is what
+/// `EventPayload::name` returns and what is persisted as the event type; structs are
+/// displayed as the name of the struct.
+///
+/// ```rust,ignore
+/// #[derive(Clone, Debug, Serialize, Deserialize, EventPayload)]
+/// pub enum HotelEvent {
+///     HotelInitialized { room_count: u32 },
+///     GuestCheckedOut { room_number: u32 },
+/// }
+///
+/// assert_eq!(HotelEvent::GuestCheckedOut { room_number: 1 }.name(), "GuestCheckedOut");
+/// ```
+///
+/// Fields holding personal data are marked with `#[event(personal_data)]`, along with the field
+/// identifying their data subject, marked with `#[event(subject)]`, to implement
+/// `EventPayload::personal_data`. Personal data fields should also be marked with
+/// `#[serde(default)]`, as they are left out of the payload once their subject is forgotten.
+/// Serde renames are not supported on the variants and fields involved.
+///
+/// ```rust,ignore
+/// #[derive(Clone, Debug, Serialize, Deserialize, EventPayload)]
+/// pub enum HotelEvent {
+///     GuestCheckedIn {
+///         room_number: u32,
+///         #[event(subject)]
+///         stay_id: String,
+///         #[event(personal_data)]
+///         #[serde(default)]
+///         guest_name: String,
+///     },
+/// }
+/// ```
+#[proc_macro_derive(EventPayload, attributes(event))]
+pub fn derive_event_payload(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_event_payload(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn expand_event_payload(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let display = match &input.data {
+        Data::Enum(data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let variant_name = &variant.ident;
+                let label = variant_name.to_string();
+                let pattern = match variant.fields {
+                    Fields::Named(_) => quote!(Self::#variant_name { .. }),
+                    Fields::Unnamed(_) => quote!(Self::#variant_name(..)),
+                    Fields::Unit => quote!(Self::#variant_name),
+                };
+                quote!(#pattern => f.write_str(#label),)
+            });
+
+            // Empty enums have no variant to match on.
+            if data.variants.is_empty() {
+                quote!(match *self {})
+            } else {
+                quote!(match self { #(#arms)* })
+            }
+        }
+        Data::Struct(_) => {
+            let label = name.to_string();
+            quote!(f.write_str(#label))
+        }
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                input,
+                "EventPayload can't be derived for unions",
+            ));
+        }
+    };
+
+    let personal_data = personal_data(input)?.map(|arms| {
+        quote! {
+            fn personal_data(&self) -> ::std::option::Option<::mini_cqrs_es::PersonalData> {
+                #[allow(unreachable_patterns)]
+                match self {
+                    #(#arms)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::std::fmt::Display for #name #ty_generics #where_clause {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                #display
+            }
+        }
+
+        impl #impl_generics ::mini_cqrs_es::EventPayload for #name #ty_generics #where_clause {
+            #personal_data
+        }
+    })
+}
+
+// Builds the match arms returning the personal data of each variant (or of the struct)
+// marking some, `None` if none does.
+fn personal_data(input: &DeriveInput) -> syn::Result<Option<Vec<proc_macro2::TokenStream>>> {
+    let mut arms = Vec::new();
+
+    let mut add_arm = |path: proc_macro2::TokenStream, prefix: String, fields: &Fields| {
+        let Some((subject, personal)) = marked_fields(fields)? else {
+            return Ok(());
+        };
+        let pointers = personal.iter().map(|field| format!("{prefix}/{field}"));
+        arms.push(quote! {
+            #path { #subject, .. } => ::std::option::Option::Some(::mini_cqrs_es::PersonalData {
+                subject: ::std::string::ToString::to_string(#subject),
+                fields: ::std::vec![#(::std::string::String::from(#pointers)),*],
+            }),
+        });
+        Ok::<_, Error>(())
+    };
+
+    match &input.data {
+        Data::Enum(data) => {
+            for variant in &data.variants {
+                let variant_name = &variant.ident;
+                add_arm(
+                    quote!(Self::#variant_name),
+                    format!("/{variant_name}"),
+                    &variant.fields,
+                )?;
+            }
+        }
+        Data::Struct(data) => add_arm(quote!(Self), String::new(), &data.fields)?,
+        Data::Union(_) => {}
+    }
+
+    Ok((!arms.is_empty()).then_some(arms))
+}
+
+// Returns the subject field and the personal data fields marked among `fields`, if any.
+fn marked_fields(fields: &Fields) -> syn::Result<Option<(Ident, Vec<Ident>)>> {
+    let mut subject = None;
+    let mut personal = Vec::new();
+
+    for field in fields {
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("event")) {
+            let Some(ident) = &field.ident else {
+                return Err(Error::new_spanned(
+                    attr,
+                    "personal data can only be marked on named fields",
+                ));
+            };
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("subject") {
+                    subject = Some(ident.clone());
+                    Ok(())
+                } else if meta.path.is_ident("personal_data") {
+                    personal.push(ident.clone());
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `subject` or `personal_data`"))
+                }
+            })?;
+        }
+    }
+
+    match (subject, personal.is_empty()) {
+        (None, true) => Ok(None),
+        (Some(subject), false) => Ok(Some((subject, personal))),
+        (None, false) => Err(Error::new_spanned(
+            &personal[0],
+            "missing subject field, mark the field identifying the data subject with \
+             `#[event(subject)]`",
+        )),
+        (Some(subject), true) => Err(Error::new_spanned(
+            subject,
+            "missing personal data, mark the fields holding it with `#[event(personal_data)]`",
+        )),
+    }
+}
+
+/// Derives `Aggregate` for a struct, generating the ID and version accessors.
+///
+/// The event type is given with `#[aggregate(event = ...)]` on the struct. The ID and the
+/// version are read from the fields marked with `#[aggregate(id)]` and
+/// `#[aggregate(version)]`, falling back to the fields named `id` and `version`; the type of
+/// the ID field is used as `Aggregate::Id`.
+///
+/// Events are applied by an inherent
+/// `async fn apply_event(&mut self, event: &Event) -> Result<(), CqrsError>` method, which can
+/// be renamed with `#[aggregate(apply = ...)]`. Invariants can be checked by an inherent
+/// `fn(&self) -> Result<(), CqrsError>` method named with `#[aggregate(invariants = ...)]`, and
+/// the schema version of snapshots set with `#[aggregate(schema_version = ...)]`.
+///
+/// ```rust,ignore
+/// #[derive(Clone, Debug, Default, Serialize, Deserialize, Aggregate)]
+/// #[aggregate(event = HotelEvent)]
+/// pub struct HotelAggregate {
+///     id: HotelId,
+///     version: u64,
+///     rooms: HashMap<u32, RoomState>,
+/// }
+///
+/// impl HotelAggregate {
+///     async fn apply_event(&mut self, event: &HotelEvent) -> Result<(), CqrsError> {
+///         // ...
+///     }
+/// }
+/// ```
+#[proc_macro_derive(Aggregate, attributes(aggregate))]
+pub fn derive_aggregate(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_aggregate(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn expand_aggregate(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut event: Option<Type> = None;
+    let mut apply: Option<Ident> = None;
+    let mut invariants: Option<Ident> = None;
+    let mut schema_version: Option<Expr> = None;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("aggregate"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("event") {
+                event = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("apply") {
+                apply = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("invariants") {
+                invariants = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("schema_version") {
+                schema_version = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `event`, `apply`, `invariants` or `schema_version`"))
+            }
+        })?;
+    }
+
+    let event = event.ok_or_else(|| {
+        Error::new_spanned(
+            name,
+            "missing event type, add `#[aggregate(event = YourEvent)]`",
+        )
+    })?;
+    let apply = apply.unwrap_or_else(|| Ident::new("apply_event", name.span()));
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    input,
+                    "Aggregate can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                input,
+                "Aggregate can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut id_field = None;
+    let mut version_field = None;
+
+    for field in fields {
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|a| a.path().is_ident("aggregate"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("id") {
+                    id_field = Some(field);
+                    Ok(())
+                } else if meta.path.is_ident("version") {
+                    version_field = Some(field);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `id` or `version`"))
+                }
+            })?;
+        }
+    }
+
+    let named = |field_name: &str| {
+        fields
+            .iter()
+            .find(|field| field.ident.as_ref().is_some_and(|i| i == field_name))
+    };
+    let id_field = id_field.or_else(|| named("id")).ok_or_else(|| {
+        Error::new_spanned(
+            name,
+            "missing ID field, add a field named `id` or mark one with `#[aggregate(id)]`",
+        )
+    })?;
+    let version_field = version_field.or_else(|| named("version")).ok_or_else(|| {
+        Error::new_spanned(
+            name,
+            "missing version field, add a field named `version` or mark one with \
+             `#[aggregate(version)]`",
+        )
+    })?;
+
+    let check_invariants = invariants.map(|invariants| {
+        quote! {
+            fn check_invariants(&self) -> ::std::result::Result<(), ::mini_cqrs_es::CqrsError> {
+                self.#invariants()
+            }
+        }
+    });
+
+    let schema_version = schema_version.map(|schema_version| {
+        quote! {
+            const SCHEMA_VERSION: u32 = #schema_version;
+        }
+    });
+
+    let id = &id_field.ident;
+    let id_type = &id_field.ty;
+    let version = &version_field.ident;
+
+    Ok(quote! {
+        impl #impl_generics ::mini_cqrs_es::Aggregate for #name #ty_generics #where_clause {
+            type Id = #id_type;
+            type Event = #event;
+
+            #schema_version
+
+            async fn apply(
+                &mut self,
+                event: &Self::Event,
+            ) -> ::std::result::Result<(), ::mini_cqrs_es::CqrsError> {
+                self.#apply(event).await
+            }
+
+            fn aggregate_id(&self) -> Self::Id {
+                ::std::clone::Clone::clone(&self.#id)
+            }
+
+            fn set_aggregate_id(&mut self, id: Self::Id) {
+                self.#id = id;
+            }
+
+            fn version(&self) -> u64 {
+                self.#version
+            }
+
+            fn set_version(&mut self, version: u64) {
+                self.#version = version;
+            }
+
+            #check_invariants
+        }
+    })
+}
@@ -0,0 +1,242 @@
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+use crate::{Aggregate, AggregateManager, CqrsError, EventStore};
+
+type CacheKey = (&'static str, String);
+
+struct CacheEntry {
+    aggregate: Box<dyn Any + Send + Sync>,
+    cached_at: Instant,
+    last_used: u64,
+}
+
+// A least-recently-used cache of aggregates, with an optional time-to-live.
+// Entries are indexed by their last use too, so that the least recently used one is found
+// without scanning the cache.
+struct AggregateCache {
+    entries: HashMap<CacheKey, CacheEntry>,
+    recency: BTreeMap<u64, CacheKey>,
+    capacity: usize,
+    ttl: Option<Duration>,
+    clock: u64,
+}
+
+impl AggregateCache {
+    fn get<A: Aggregate>(&mut self, key: &CacheKey) -> Option<A> {
+        let expired = match (self.entries.get(key), self.ttl) {
+            (None, _) => return None,
+            (Some(entry), Some(ttl)) => entry.cached_at.elapsed() > ttl,
+            (Some(_), None) => false,
+        };
+
+        if expired {
+            self.remove(key);
+            return None;
+        }
+
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        let key = self
+            .recency
+            .remove(&entry.last_used)
+            .unwrap_or_else(|| key.clone());
+        entry.last_used = self.clock;
+        self.recency.insert(self.clock, key);
+        entry.aggregate.downcast_ref::<A>().cloned()
+    }
+
+    fn insert<A: Aggregate>(&mut self, key: CacheKey, aggregate: A) {
+        self.clock += 1;
+        self.recency.insert(self.clock, key.clone());
+        let replaced = self.entries.insert(
+            key,
+            CacheEntry {
+                aggregate: Box::new(aggregate),
+                cached_at: Instant::now(),
+                last_used: self.clock,
+            },
+        );
+        if let Some(replaced) = replaced {
+            self.recency.remove(&replaced.last_used);
+        }
+
+        while self.entries.len() > self.capacity {
+            match self.recency.pop_first() {
+                Some((_, key)) => self.entries.remove(&key),
+                None => break,
+            };
+        }
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+        }
+    }
+}
+
+/// An aggregate manager decorator keeping recently used aggregates in memory.
+///
+/// Aggregates are cached by type and ID, with a least-recently-used eviction policy and an
+/// optional time-to-live. By default, before returning a cached aggregate, its version is
+/// revalidated against the event store, so that aggregates modified by other writers (other
+/// processes, other managers) are reloaded through the wrapped manager instead of being served
+/// stale. Single-writer deployments can skip this round trip with
+/// [`CachedAggregateManager::with_revalidation`].
+///
+/// ```rust,ignore
+/// let manager = CachedAggregateManager::new(SimpleAggregateManager::new(store.clone()), store)
+///     .with_capacity(10_000)
+///     .with_ttl(Duration::from_secs(300));
+/// ```
+pub struct CachedAggregateManager<AM, ES>
+where
+    AM: AggregateManager,
+    ES: EventStore,
+{
+    inner: AM,
+    event_store: ES,
+    cache: Mutex<AggregateCache>,
+    revalidate: bool,
+}
+
+impl<AM, ES> CachedAggregateManager<AM, ES>
+where
+    AM: AggregateManager,
+    ES: EventStore,
+{
+    /// Creates a new cache holding up to 1000 aggregates, without time-to-live.
+    pub fn new(inner: AM, event_store: ES) -> Self {
+        Self {
+            inner,
+            event_store,
+            cache: Mutex::new(AggregateCache {
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                capacity: 1000,
+                ttl: None,
+                clock: 0,
+            }),
+            revalidate: true,
+        }
+    }
+
+    /// Sets the maximum number of cached aggregates.
+    pub fn with_capacity(self, capacity: usize) -> Self {
+        self.cache.lock().unwrap().capacity = capacity;
+        self
+    }
+
+    /// Sets how long an aggregate stays cached after being loaded or stored.
+    pub fn with_ttl(self, ttl: Duration) -> Self {
+        self.cache.lock().unwrap().ttl = Some(ttl);
+        self
+    }
+
+    /// Sets whether cached aggregates are revalidated against the version of their stream
+    /// before being returned. Defaults to `true`.
+    ///
+    /// Only disable it when this manager is the only writer of its streams: aggregates
+    /// modified by other writers are then served stale until they expire (see
+    /// [`CachedAggregateManager::with_ttl`]) or are invalidated, and their commands fail
+    /// with [`CqrsError::Conflict`].
+    pub fn with_revalidation(mut self, revalidate: bool) -> Self {
+        self.revalidate = revalidate;
+        self
+    }
+
+    /// Removes an aggregate from the cache.
+    pub fn invalidate<A: Aggregate>(&self, aggregate_id: &A::Id) {
+        self.cache
+            .lock()
+            .unwrap()
+            .remove(&cache_key::<A>(aggregate_id));
+    }
+
+    /// Returns the number of cached aggregates.
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().entries.len()
+    }
+
+    /// Returns `true` if no aggregate is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn cache<A: Aggregate>(&self, aggregate: &A) {
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(cache_key::<A>(&aggregate.aggregate_id()), aggregate.clone());
+    }
+}
+
+fn cache_key<A: Aggregate>(aggregate_id: &A::Id) -> CacheKey {
+    (std::any::type_name::<A>(), aggregate_id.to_string())
+}
+
+impl<AM, ES> AggregateManager for CachedAggregateManager<AM, ES>
+where
+    AM: AggregateManager,
+    ES: EventStore,
+{
+    async fn load<A>(&self, aggregate_id: &A::Id) -> Result<A, CqrsError>
+    where
+        A: Aggregate,
+    {
+        let key = cache_key::<A>(aggregate_id);
+        let cached = self.cache.lock().unwrap().get::<A>(&key);
+
+        if let Some(aggregate) = cached {
+            if !self.revalidate {
+                return Ok(aggregate);
+            }
+
+            let current_version = self
+                .event_store
+                .stream_version(key.0, &key.1)
+                .await
+                .map_err(CqrsError::into_event_store)?;
+
+            if current_version == aggregate.version() {
+                return Ok(aggregate);
+            }
+        }
+
+        let aggregate = self.inner.load::<A>(aggregate_id).await?;
+        self.cache(&aggregate);
+        Ok(aggregate)
+    }
+
+    async fn load_at_version<A>(&self, aggregate_id: &A::Id, version: u64) -> Result<A, CqrsError>
+    where
+        A: Aggregate,
+    {
+        self.inner.load_at_version(aggregate_id, version).await
+    }
+
+    async fn load_as_of<A>(
+        &self,
+        aggregate_id: &A::Id,
+        timestamp: DateTime<Utc>,
+    ) -> Result<A, CqrsError>
+    where
+        A: Aggregate,
+    {
+        self.inner.load_as_of(aggregate_id, timestamp).await
+    }
+
+    async fn store<A>(&self, aggregate: &A) -> Result<(), CqrsError>
+    where
+        A: Aggregate,
+    {
+        self.inner.store(aggregate).await?;
+        self.cache(aggregate);
+        Ok(())
+    }
+}
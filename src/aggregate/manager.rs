@@ -1,6 +1,9 @@
 use std::future::Future;
 
-use crate::{Aggregate, AggregateSnapshot, CqrsError, EventStore, SnapshotStore};
+use chrono::{DateTime, Utc};
+
+use crate::archive::check_history;
+use crate::{Aggregate, AggregateSnapshot, CqrsError, EventStore, SnapshotStore, StoredEvent};
 
 /// The `AggregateManager` trait defines the behavior for loading and storing the state of aggregates.
 ///
@@ -11,6 +14,38 @@ pub trait AggregateManager: Send + Sync {
     where
         A: Aggregate;
 
+    /// Loads an aggregate as it was at the given version, replaying its events up to and
+    /// including `version`.
+    ///
+    /// The default implementation returns an error, as not every manager has access to the
+    /// event history.
+    fn load_at_version<A>(
+        &self,
+        _aggregate_id: &A::Id,
+        _version: u64,
+    ) -> impl Future<Output = Result<A, CqrsError>> + Send
+    where
+        A: Aggregate,
+    {
+        async { Err(time_travel_unsupported()) }
+    }
+
+    /// Loads an aggregate as it was at the given point in time, replaying the events recorded
+    /// at or before `timestamp`.
+    ///
+    /// The default implementation returns an error, as not every manager has access to the
+    /// event history.
+    fn load_as_of<A>(
+        &self,
+        _aggregate_id: &A::Id,
+        _timestamp: DateTime<Utc>,
+    ) -> impl Future<Output = Result<A, CqrsError>> + Send
+    where
+        A: Aggregate,
+    {
+        async { Err(time_travel_unsupported()) }
+    }
+
     /// Stores an aggregate's state. Default implementation is a no-op.
     fn store<A>(&self, _aggregate: &A) -> impl Future<Output = Result<(), CqrsError>> + Send
     where
@@ -22,6 +57,9 @@ pub trait AggregateManager: Send + Sync {
 
 /// A simple implementation of the `AggregateManager` trait. It loads aggregates
 /// by replaying their events from the associated `EventStore`, but doesn't implement any storage logic.
+///
+/// A stream that doesn't exist yields a default aggregate, while any failure reported by the
+/// event store is propagated as [`CqrsError::EventStore`].
 pub struct SimpleAggregateManager<ES>
 where
     ES: EventStore,
@@ -43,45 +81,169 @@ where
     ES: EventStore,
 {
     async fn load<A: Aggregate>(&self, aggregate_id: &A::Id) -> Result<A, CqrsError> {
-        let mut aggregate = A::default();
-        aggregate.set_aggregate_id(aggregate_id.clone());
-        let aggregate_id_str = aggregate_id.to_string();
+        let loaded = self
+            .event_store
+            .load_events(std::any::type_name::<A>(), &aggregate_id.to_string())
+            .await;
 
-        if let Ok((events, version)) = self
+        replay(aggregate_id, loaded).await
+    }
+
+    async fn load_at_version<A: Aggregate>(
+        &self,
+        aggregate_id: &A::Id,
+        version: u64,
+    ) -> Result<A, CqrsError> {
+        let loaded = self
             .event_store
-            .load_events(std::any::type_name::<A>(), &aggregate_id_str)
-            .await
-        {
-            aggregate.apply_events(&events).await?;
-            aggregate.set_version(version);
-        }
+            .load_events_to_version(
+                std::any::type_name::<A>(),
+                &aggregate_id.to_string(),
+                version,
+            )
+            .await;
+
+        replay_past(&self.event_store, aggregate_id, loaded).await
+    }
+
+    async fn load_as_of<A: Aggregate>(
+        &self,
+        aggregate_id: &A::Id,
+        timestamp: DateTime<Utc>,
+    ) -> Result<A, CqrsError> {
+        let loaded = self
+            .event_store
+            .load_events_as_of(
+                std::any::type_name::<A>(),
+                &aggregate_id.to_string(),
+                timestamp,
+            )
+            .await;
 
-        Ok(aggregate)
+        replay_past(&self.event_store, aggregate_id, loaded).await
     }
 }
 
+// Rebuilds an aggregate from the result of an event store load, refusing to replay a
+// truncated stream.
+async fn replay<A: Aggregate>(
+    aggregate_id: &A::Id,
+    loaded: Result<(Vec<StoredEvent>, u64), CqrsError>,
+) -> Result<A, CqrsError> {
+    let (events, version) = loaded.map_err(CqrsError::into_event_store)?;
+    check_history(aggregate_id, &events)?;
+
+    let mut aggregate = A::default();
+    aggregate.set_aggregate_id(aggregate_id.clone());
+    aggregate.apply_events(&events).await?;
+    aggregate.set_version(version);
+
+    Ok(aggregate)
+}
+
+// Rebuilds an aggregate as it was in the past. Nothing is loaded before the first event left
+// in a stream, so an empty load is checked against the whole stream, which may be truncated.
+async fn replay_past<A, ES>(
+    event_store: &ES,
+    aggregate_id: &A::Id,
+    loaded: Result<(Vec<StoredEvent>, u64), CqrsError>,
+) -> Result<A, CqrsError>
+where
+    A: Aggregate,
+    ES: EventStore,
+{
+    if let Ok((events, _)) = &loaded
+        && events.is_empty()
+    {
+        let (events, _) = event_store
+            .load_events(std::any::type_name::<A>(), &aggregate_id.to_string())
+            .await
+            .map_err(CqrsError::into_event_store)?;
+        check_history(aggregate_id, &events)?;
+    }
+
+    replay(aggregate_id, loaded).await
+}
+
+fn time_travel_unsupported() -> CqrsError {
+    CqrsError::Other(anyhow::anyhow!(
+        "time-travel loading is not supported by this aggregate manager"
+    ))
+}
+
 /// An aggregate manager that uses a snapshot store to load and store aggregates.
 ///
 /// This implementation optimizes the loading of aggregates by utilizing a `SnapshotStore`.
 /// Snapshots capture the aggregate state at specific points, reducing the need to replay
 /// all events from the beginning.
-pub struct SnapshotAggregateManager<SS>
+///
+/// Created with [`SnapshotAggregateManager::new`], it loads aggregates from their snapshots
+/// alone: a missing snapshot yields a default aggregate, a snapshot taken with another
+/// [`Aggregate::SCHEMA_VERSION`] fails with [`CqrsError::SnapshotStore`], and time-travel
+/// loading isn't supported.
+///
+/// With an event store set by [`SnapshotAggregateManager::with_event_store`], only the events
+/// appended after the snapshot are replayed from the `EventStore`. Snapshots taken with
+/// another schema version, or ahead of the stream, are discarded and the aggregate is rebuilt
+/// from all its events; the next stored snapshot replaces them. Streams truncated by a
+/// [`StreamArchiver`] can't be rebuilt that way and fail with [`CqrsError::TruncatedStream`].
+///
+/// Failures reported by the snapshot store are propagated as [`CqrsError::SnapshotStore`], and
+/// failures reported by the event store as [`CqrsError::EventStore`].
+///
+/// [`StreamArchiver`]: crate::StreamArchiver
+pub struct SnapshotAggregateManager<SS, ES = NoEventStore>
 where
     SS: SnapshotStore,
 {
     snapshot_store: SS,
+    event_store: ES,
 }
 
+/// The event store of a [`SnapshotAggregateManager`] loading aggregates from their snapshots
+/// alone, until an event store is set.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoEventStore;
+
 impl<SS> SnapshotAggregateManager<SS>
 where
     SS: SnapshotStore,
 {
     pub fn new(snapshot_store: SS) -> Self {
-        Self { snapshot_store }
+        Self {
+            snapshot_store,
+            event_store: NoEventStore,
+        }
+    }
+}
+
+impl<SS, ES> SnapshotAggregateManager<SS, ES>
+where
+    SS: SnapshotStore,
+{
+    /// Sets the event store from which the events following the snapshots are replayed.
+    pub fn with_event_store<E>(self, event_store: E) -> SnapshotAggregateManager<SS, E>
+    where
+        E: EventStore,
+    {
+        SnapshotAggregateManager {
+            snapshot_store: self.snapshot_store,
+            event_store,
+        }
+    }
+
+    async fn save_snapshot<A>(&self, aggregate: &A) -> Result<(), CqrsError>
+    where
+        A: Aggregate,
+    {
+        self.snapshot_store
+            .save_snapshot::<A>(AggregateSnapshot::new(aggregate, Some(aggregate.version()))?)
+            .await?;
+        Ok(())
     }
 }
 
-impl<SS> AggregateManager for SnapshotAggregateManager<SS>
+impl<SS> AggregateManager for SnapshotAggregateManager<SS, NoEventStore>
 where
     SS: SnapshotStore,
 {
@@ -89,18 +251,29 @@ where
     where
         A: Aggregate,
     {
-        if let Ok(snapshot) = self
+        let snapshot = self
             .snapshot_store
             .load_snapshot::<A>(aggregate_id)
             .await
-        {
-            let mut aggregate = snapshot.get_payload::<A>()?;
-            aggregate.set_version(snapshot.version);
-            Ok(aggregate)
-        } else {
-            let mut aggregate = A::default();
-            aggregate.set_aggregate_id(aggregate_id.clone());
-            Ok(aggregate)
+            .map_err(CqrsError::into_snapshot_store)?;
+
+        match snapshot {
+            Some(snapshot) if !snapshot.is_compatible() => Err(CqrsError::SnapshotStore(format!(
+                "the snapshot of aggregate {aggregate_id} was taken with schema version {}, \
+                 expected {}",
+                snapshot.schema_version,
+                A::SCHEMA_VERSION
+            ))),
+            Some(snapshot) => {
+                let mut aggregate = snapshot.get_payload::<A>()?;
+                aggregate.set_version(snapshot.version);
+                Ok(aggregate)
+            }
+            None => {
+                let mut aggregate = A::default();
+                aggregate.set_aggregate_id(aggregate_id.clone());
+                Ok(aggregate)
+            }
         }
     }
 
@@ -108,9 +281,91 @@ where
     where
         A: Aggregate,
     {
-        self.snapshot_store
-            .save_snapshot::<A>(AggregateSnapshot::new(aggregate, Some(aggregate.version()))?)
-            .await?;
-        Ok(())
+        self.save_snapshot(aggregate).await
+    }
+}
+
+impl<SS, ES> AggregateManager for SnapshotAggregateManager<SS, ES>
+where
+    SS: SnapshotStore,
+    ES: EventStore,
+{
+    async fn load<A>(&self, aggregate_id: &A::Id) -> Result<A, CqrsError>
+    where
+        A: Aggregate,
+    {
+        let aggregate_type = std::any::type_name::<A>();
+        let stream_id = aggregate_id.to_string();
+
+        let snapshot = self
+            .snapshot_store
+            .load_snapshot::<A>(aggregate_id)
+            .await
+            .map_err(CqrsError::into_snapshot_store)?
+            .filter(AggregateSnapshot::is_compatible);
+
+        if let Some(snapshot) = snapshot {
+            let (events, version) = self
+                .event_store
+                .load_events_after(aggregate_type, &stream_id, snapshot.version)
+                .await
+                .map_err(CqrsError::into_event_store)?;
+
+            if snapshot.version <= version {
+                let mut aggregate = snapshot.get_payload::<A>()?;
+                aggregate.apply_events(&events).await?;
+                aggregate.set_version(version);
+                return Ok(aggregate);
+            }
+        }
+
+        let loaded = self
+            .event_store
+            .load_events(aggregate_type, &stream_id)
+            .await;
+        replay(aggregate_id, loaded).await
+    }
+
+    async fn load_at_version<A>(&self, aggregate_id: &A::Id, version: u64) -> Result<A, CqrsError>
+    where
+        A: Aggregate,
+    {
+        let loaded = self
+            .event_store
+            .load_events_to_version(
+                std::any::type_name::<A>(),
+                &aggregate_id.to_string(),
+                version,
+            )
+            .await;
+
+        replay_past(&self.event_store, aggregate_id, loaded).await
+    }
+
+    async fn load_as_of<A>(
+        &self,
+        aggregate_id: &A::Id,
+        timestamp: DateTime<Utc>,
+    ) -> Result<A, CqrsError>
+    where
+        A: Aggregate,
+    {
+        let loaded = self
+            .event_store
+            .load_events_as_of(
+                std::any::type_name::<A>(),
+                &aggregate_id.to_string(),
+                timestamp,
+            )
+            .await;
+
+        replay_past(&self.event_store, aggregate_id, loaded).await
+    }
+
+    async fn store<A>(&self, aggregate: &A) -> Result<(), CqrsError>
+    where
+        A: Aggregate,
+    {
+        self.save_snapshot(aggregate).await
     }
 }
@@ -8,6 +8,7 @@ use serde::{de::DeserializeOwned, Serialize};
 
 use crate::{CqrsError, EventPayload, StoredEvent};
 
+pub mod cache;
 pub mod manager;
 pub mod snapshot;
 
@@ -15,15 +16,32 @@ pub mod snapshot;
 /// domain entity and can be modified by applying events.
 ///
 /// Aggregates track their version for optimistic concurrency control.
-pub trait Aggregate: Clone + Debug + Default + Sync + Send + Serialize + DeserializeOwned {
+pub trait Aggregate:
+    Clone + Debug + Default + Sync + Send + Serialize + DeserializeOwned + 'static
+{
     /// The type of identity used by this aggregate.
     type Id: Clone + Debug + Display + FromStr + Eq + Hash + Send + Sync + 'static;
 
     /// The type of event that this aggregate can handle.
     type Event: EventPayload + Send + Sync;
 
+    /// The version of the aggregate's serialized state, recorded in its snapshots.
+    ///
+    /// Bump it whenever a change to the aggregate makes its existing snapshots unusable (a new
+    /// field, a field whose meaning changed, etc.): snapshots taken with another schema
+    /// version are discarded, and the aggregate is rebuilt from its events. The snapshots of
+    /// streams truncated by a [`StreamArchiver`] must be rebuilt with
+    /// [`StreamArchiver::rebuild_snapshot`] instead.
+    ///
+    /// [`StreamArchiver`]: crate::StreamArchiver
+    /// [`StreamArchiver::rebuild_snapshot`]: crate::StreamArchiver::rebuild_snapshot
+    const SCHEMA_VERSION: u32 = 1;
+
     /// Applies an event to the aggregate's state.
-    fn apply(&mut self, event: &Self::Event) -> impl Future<Output = ()> + Send;
+    ///
+    /// Returns an error if the event can't be applied (e.g. it's impossible in the current
+    /// state), rather than panicking or silently ignoring it.
+    fn apply(&mut self, event: &Self::Event) -> impl Future<Output = Result<(), CqrsError>> + Send;
 
     /// Returns the aggregate's ID.
     fn aggregate_id(&self) -> Self::Id;
@@ -39,14 +57,36 @@ pub trait Aggregate: Clone + Debug + Default + Sync + Send + Serialize + Deseria
     /// Sets the version of the aggregate.
     fn set_version(&mut self, _version: u64) {}
 
+    /// Checks the invariants of the aggregate's state (e.g. occupied rooms never exceed the
+    /// number of rooms).
+    ///
+    /// Invariants are checked after applying the events emitted by a command and before they
+    /// are saved, so that a handler bug can't turn an invalid state into permanent history.
+    /// Errors are reported as [`CqrsError::CommandInvariant`].
+    fn check_invariants(&self) -> Result<(), CqrsError> {
+        Ok(())
+    }
+
     /// Applies a sequence of events to the aggregate's state.
+    ///
+    /// Stops at the first event that can't be deserialized or applied, returning a
+    /// [`CqrsError::EventApply`] identifying it.
     fn apply_events(
         &mut self,
         events: &[StoredEvent],
     ) -> impl Future<Output = Result<(), CqrsError>> + Send {
         async {
             for e in events.iter() {
-                self.apply(&e.get_payload::<Self::Event>()?).await;
+                let applied = match e.get_payload::<Self::Event>() {
+                    Ok(payload) => self.apply(&payload).await,
+                    Err(error) => Err(error),
+                };
+
+                applied.map_err(|source| CqrsError::EventApply {
+                    event_id: e.id.clone(),
+                    version: e.version,
+                    source: Box::new(source),
+                })?;
             }
             Ok(())
         }
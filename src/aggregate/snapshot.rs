@@ -4,6 +4,10 @@ use crate::{Aggregate, CqrsError};
 
 /// The `SnapshotStore` trait defines the behavior for storing and loading aggregate snapshots.
 ///
+/// Stores must keep the serialized payload and the schema version of snapshots as they are,
+/// and restore them with [`AggregateSnapshot::from_stored`], so that snapshots of an older
+/// schema can still be loaded (and discarded) after the aggregate changed.
+///
 /// All methods take `&self` to allow concurrent access.
 pub trait SnapshotStore: Send + Sync {
     /// Saves an aggregate snapshot to the snapshot store.
@@ -15,10 +19,13 @@ pub trait SnapshotStore: Send + Sync {
         T: Aggregate;
 
     /// Loads an aggregate snapshot from the snapshot store.
+    ///
+    /// Returns `Ok(None)` when no snapshot exists for the aggregate; errors are reserved for
+    /// actual store failures.
     fn load_snapshot<T>(
         &self,
         aggregate_id: &T::Id,
-    ) -> impl Future<Output = Result<AggregateSnapshot<T>, CqrsError>> + Send
+    ) -> impl Future<Output = Result<Option<AggregateSnapshot<T>>, CqrsError>> + Send
     where
         T: Aggregate;
 }
@@ -38,6 +45,10 @@ where
     /// The version of the aggregate snapshot.
     pub version: u64,
 
+    /// The schema version of the aggregate when the snapshot was taken (see
+    /// [`Aggregate::SCHEMA_VERSION`]).
+    pub schema_version: u32,
+
     /// A marker to ensure type safety.
     marker: std::marker::PhantomData<T>,
 }
@@ -54,10 +65,38 @@ where
             aggregate_id: aggregate.aggregate_id(),
             payload: serde_json::to_value(aggregate)?,
             version,
+            schema_version: T::SCHEMA_VERSION,
             marker: std::marker::PhantomData,
         })
     }
 
+    /// Restores a snapshot read from a snapshot store, without deserializing its payload.
+    pub fn from_stored(
+        aggregate_id: T::Id,
+        payload: serde_json::Value,
+        version: u64,
+        schema_version: u32,
+    ) -> Self {
+        Self {
+            aggregate_id,
+            payload,
+            version,
+            schema_version,
+            marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the serialized aggregate.
+    pub fn payload(&self) -> &serde_json::Value {
+        &self.payload
+    }
+
+    /// Returns `true` if the snapshot was taken with the current schema version of the
+    /// aggregate.
+    pub fn is_compatible(&self) -> bool {
+        self.schema_version == T::SCHEMA_VERSION
+    }
+
     /// Gets the aggregate from the snapshot.
     pub fn get_payload<A>(&self) -> Result<A, CqrsError>
     where
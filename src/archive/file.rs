@@ -0,0 +1,129 @@
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::EventArchive;
+use crate::{CqrsError, StoredEvent};
+
+/// An [`EventArchive`] appending events to a [JSON Lines](https://jsonlines.org) file, one
+/// event per line.
+///
+/// Events archived twice are written twice and skipped when reading. Reads scan the whole
+/// file, which suits cold data read for audits and occasional rebuilds.
+///
+/// File operations use blocking I/O (appends are synced to disk) and block the thread
+/// polling the future while they run. Don't use the archive on an executor shared with
+/// latency-sensitive tasks; run archiving from a dedicated thread or a blocking task of your
+/// runtime instead.
+///
+/// ```rust,ignore
+/// let archive = JsonlFileArchive::new("/var/lib/hotel/archive.jsonl");
+/// ```
+#[derive(Clone)]
+pub struct JsonlFileArchive {
+    file: Arc<ArchiveFile>,
+}
+
+impl JsonlFileArchive {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            file: Arc::new(ArchiveFile {
+                path: path.into(),
+                lock: Mutex::new(()),
+            }),
+        }
+    }
+}
+
+struct ArchiveFile {
+    path: PathBuf,
+    // Serializes appends, so that concurrent batches aren't interleaved. It guards no data,
+    // so a poisoned lock is still usable.
+    lock: Mutex<()>,
+}
+
+impl ArchiveFile {
+    fn append(&self, lines: &str) -> Result<(), CqrsError> {
+        let _lock = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(archive_error)?;
+        file.write_all(lines.as_bytes()).map_err(archive_error)?;
+        file.sync_data().map_err(archive_error)
+    }
+
+    // Reads every archived event matching `filter`, skipping the ones archived more than once.
+    fn read(&self, filter: impl Fn(&StoredEvent) -> bool) -> Result<Vec<StoredEvent>, CqrsError> {
+        let _lock = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(archive_error(error)),
+        };
+
+        let mut seen = HashSet::new();
+        let mut events = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(archive_error)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let event: StoredEvent = serde_json::from_str(&line).map_err(|e| {
+                CqrsError::EventArchive(format!("Failed to parse archived event JSON: {e}"))
+            })?;
+            if filter(&event) && seen.insert(event.id.clone()) {
+                events.push(event);
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+fn archive_error(error: std::io::Error) -> CqrsError {
+    CqrsError::EventArchive(error.to_string())
+}
+
+impl EventArchive for JsonlFileArchive {
+    async fn archive_events(&self, events: &[StoredEvent]) -> Result<(), CqrsError> {
+        let mut lines = String::new();
+        for event in events {
+            lines.push_str(&serde_json::to_string(event)?);
+            lines.push('\n');
+        }
+
+        self.file.append(&lines)
+    }
+
+    async fn load_archived_events(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+    ) -> Result<Vec<StoredEvent>, CqrsError> {
+        let mut events = self
+            .file
+            .read(|e| e.aggregate_type == aggregate_type && e.aggregate_id == aggregate_id)?;
+        events.sort_by_key(|e| e.version);
+        Ok(events)
+    }
+
+    async fn load_archived_events_after(
+        &self,
+        after_sequence: i64,
+        limit: usize,
+    ) -> Result<Vec<StoredEvent>, CqrsError> {
+        let mut events = self
+            .file
+            .read(|e| e.global_sequence.unwrap_or(0) > after_sequence)?;
+        events.sort_by_key(|e| e.global_sequence);
+        events.truncate(limit);
+        Ok(events)
+    }
+}
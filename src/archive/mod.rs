@@ -0,0 +1,265 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::str::FromStr;
+
+use crate::{Aggregate, AggregateSnapshot, CqrsError, EventStore, SnapshotStore, StoredEvent};
+
+mod file;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use file::JsonlFileArchive;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteEventArchive;
+
+/// The `EventArchive` trait defines a cold store for events moved out of the event store,
+/// keeping them readable for audits and for rebuilding projections from the global stream.
+///
+/// Archiving must be idempotent: events already archived (identified by their ID) can be
+/// archived again, e.g. after a crash between archiving them and deleting them from the
+/// event store, without being duplicated.
+pub trait EventArchive: Send + Sync {
+    /// Archives events.
+    fn archive_events(
+        &self,
+        events: &[StoredEvent],
+    ) -> impl Future<Output = Result<(), CqrsError>> + Send;
+
+    /// Loads the archived events of a stream, in version order.
+    fn load_archived_events(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+    ) -> impl Future<Output = Result<Vec<StoredEvent>, CqrsError>> + Send;
+
+    /// Loads up to `limit` archived events whose global sequence follows `after_sequence`,
+    /// across streams, in global sequence order.
+    fn load_archived_events_after(
+        &self,
+        after_sequence: i64,
+        limit: usize,
+    ) -> impl Future<Output = Result<Vec<StoredEvent>, CqrsError>> + Send;
+}
+
+/// Moves the events preceding the latest snapshot of a stream from the event store to an
+/// [`EventArchive`], so that long-lived streams stay short.
+///
+/// The snapshot must be confirmed first: it must have the current schema version and match
+/// the state replayed from the whole history of the stream (archived and live events), so
+/// that no information is lost by truncating the stream. The event at the snapshot version
+/// stays in the event store, keeping the stream at its current version.
+///
+/// Truncated streams must be loaded with a [`SnapshotAggregateManager`], from their snapshot.
+/// Replaying them from their events alone fails with [`CqrsError::TruncatedStream`]: this
+/// includes time-travel loading, the [`ReplayVerifier`], and the rebuild of snapshots taken
+/// with another schema version, which must be done with
+/// [`StreamArchiver::rebuild_snapshot`]. The whole history remains available through
+/// [`StreamArchiver::load_history`].
+///
+/// ```rust,ignore
+/// let archiver = StreamArchiver::new(event_store, snapshot_store, SqliteEventArchive::new(pool));
+/// let archived = archiver.archive_all::<HotelAggregate>().await?;
+/// ```
+///
+/// [`SnapshotAggregateManager`]: crate::SnapshotAggregateManager
+/// [`ReplayVerifier`]: crate::ReplayVerifier
+pub struct StreamArchiver<ES, SS, EA>
+where
+    ES: EventStore,
+    SS: SnapshotStore,
+    EA: EventArchive,
+{
+    event_store: ES,
+    snapshot_store: SS,
+    archive: EA,
+}
+
+impl<ES, SS, EA> StreamArchiver<ES, SS, EA>
+where
+    ES: EventStore,
+    SS: SnapshotStore,
+    EA: EventArchive,
+{
+    pub fn new(event_store: ES, snapshot_store: SS, archive: EA) -> Self {
+        Self {
+            event_store,
+            snapshot_store,
+            archive,
+        }
+    }
+
+    /// Archives the events preceding the latest snapshot of an aggregate. Returns the number
+    /// of archived events, `0` if the aggregate has no compatible snapshot.
+    ///
+    /// Returns [`CqrsError::SnapshotStore`] if the snapshot doesn't match the events, leaving
+    /// the stream untouched. The event store may also refuse to delete the events, e.g. while
+    /// they wait in an outbox; archiving them again later is safe.
+    pub async fn archive<A>(&self, aggregate_id: &A::Id) -> Result<usize, CqrsError>
+    where
+        A: Aggregate,
+    {
+        let aggregate_type = std::any::type_name::<A>();
+        let stream_id = aggregate_id.to_string();
+
+        let snapshot = self
+            .snapshot_store
+            .load_snapshot::<A>(aggregate_id)
+            .await
+            .map_err(CqrsError::into_snapshot_store)?
+            .filter(AggregateSnapshot::is_compatible);
+
+        // Nothing precedes a snapshot taken at the first version.
+        let Some(snapshot) = snapshot.filter(|s| s.version > 1) else {
+            return Ok(0);
+        };
+
+        let mut history = self.load_history::<A>(aggregate_id).await?;
+        history.retain(|e| e.version <= snapshot.version);
+        confirm(aggregate_id, &snapshot, &history).await?;
+
+        let (events, _) = self
+            .event_store
+            .load_events_to_version(aggregate_type, &stream_id, snapshot.version - 1)
+            .await
+            .map_err(CqrsError::into_event_store)?;
+        if events.is_empty() {
+            return Ok(0);
+        }
+
+        self.archive
+            .archive_events(&events)
+            .await
+            .map_err(CqrsError::into_event_archive)?;
+        self.event_store
+            .delete_events_before(aggregate_type, &stream_id, snapshot.version)
+            .await
+            .map_err(CqrsError::into_event_store)
+    }
+
+    /// Archives the events preceding the latest snapshot of every aggregate of type `A`, as
+    /// listed by [`EventStore::stream_ids`]. Returns the number of archived events.
+    pub async fn archive_all<A>(&self) -> Result<usize, CqrsError>
+    where
+        A: Aggregate,
+    {
+        let aggregate_type = std::any::type_name::<A>();
+        let stream_ids = self
+            .event_store
+            .stream_ids(aggregate_type)
+            .await
+            .map_err(CqrsError::into_event_store)?;
+
+        let mut archived = 0;
+        for stream_id in stream_ids {
+            let aggregate_id = A::Id::from_str(&stream_id).map_err(|_| {
+                CqrsError::EventStore(format!(
+                    "invalid aggregate id `{stream_id}` for {aggregate_type}"
+                ))
+            })?;
+            archived += self.archive::<A>(&aggregate_id).await?;
+        }
+
+        Ok(archived)
+    }
+
+    /// Loads the whole history of an aggregate, archived and live events, in version order.
+    pub async fn load_history<A>(&self, aggregate_id: &A::Id) -> Result<Vec<StoredEvent>, CqrsError>
+    where
+        A: Aggregate,
+    {
+        let aggregate_type = std::any::type_name::<A>();
+        let stream_id = aggregate_id.to_string();
+
+        let mut history = self
+            .archive
+            .load_archived_events(aggregate_type, &stream_id)
+            .await
+            .map_err(CqrsError::into_event_archive)?;
+        let last_archived = history.last().map(|e| e.version).unwrap_or(0);
+
+        let (live, _) = self
+            .event_store
+            .load_events_after(aggregate_type, &stream_id, last_archived)
+            .await
+            .map_err(CqrsError::into_event_store)?;
+        history.extend(live);
+
+        Ok(history)
+    }
+
+    /// Rebuilds an aggregate from its whole history and saves it as its latest snapshot, e.g.
+    /// after bumping [`Aggregate::SCHEMA_VERSION`], as the snapshot of a truncated stream
+    /// can't be rebuilt from the event store alone. Returns the rebuilt aggregate.
+    pub async fn rebuild_snapshot<A>(&self, aggregate_id: &A::Id) -> Result<A, CqrsError>
+    where
+        A: Aggregate,
+    {
+        let history = self.load_history::<A>(aggregate_id).await?;
+        check_history(aggregate_id, &history)?;
+
+        let mut aggregate = A::default();
+        aggregate.set_aggregate_id(aggregate_id.clone());
+        aggregate.apply_events(&history).await?;
+        aggregate.set_version(history.last().map(|e| e.version).unwrap_or(0));
+
+        if aggregate.version() > 0 {
+            self.snapshot_store
+                .save_snapshot(AggregateSnapshot::new(
+                    &aggregate,
+                    Some(aggregate.version()),
+                )?)
+                .await
+                .map_err(CqrsError::into_snapshot_store)?;
+        }
+        Ok(aggregate)
+    }
+}
+
+// Checks that the events of a stream start at its first version: the events preceding a
+// snapshot may have been archived, and replaying the rest from the default state would
+// yield a wrong aggregate.
+pub(crate) fn check_history(
+    aggregate_id: &impl Display,
+    events: &[StoredEvent],
+) -> Result<(), CqrsError> {
+    match events.first() {
+        Some(first) if first.version > 1 => Err(CqrsError::TruncatedStream {
+            aggregate_id: aggregate_id.to_string(),
+            version: first.version,
+        }),
+        _ => Ok(()),
+    }
+}
+
+// Checks that replaying the history of an aggregate up to the snapshot version yields the
+// snapshot.
+async fn confirm<A: Aggregate>(
+    aggregate_id: &A::Id,
+    snapshot: &AggregateSnapshot<A>,
+    history: &[StoredEvent],
+) -> Result<(), CqrsError> {
+    let unconfirmed = || {
+        CqrsError::SnapshotStore(format!(
+            "the snapshot of {} {aggregate_id} at version {} doesn't match its events",
+            std::any::type_name::<A>(),
+            snapshot.version
+        ))
+    };
+
+    if history.len() as u64 != snapshot.version {
+        return Err(unconfirmed());
+    }
+
+    let mut replayed = A::default();
+    replayed.set_aggregate_id(aggregate_id.clone());
+    replayed.apply_events(history).await?;
+    replayed.set_version(snapshot.version);
+
+    let mut restored = snapshot.get_payload::<A>()?;
+    restored.set_version(snapshot.version);
+
+    if serde_json::to_value(&replayed)? != serde_json::to_value(&restored)? {
+        return Err(unconfirmed());
+    }
+    Ok(())
+}
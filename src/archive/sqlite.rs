@@ -0,0 +1,137 @@
+use sqlx::SqlitePool;
+
+use super::EventArchive;
+use crate::{CqrsError, StoredEvent};
+
+/// An [`EventArchive`] backed by SQLite via sqlx, available with the `sqlite` feature.
+///
+/// Events are kept serialized in an `archived_events` table, which can live in the database
+/// of the event store or in a separate one. Events archived twice are kept once.
+///
+/// ```rust,ignore
+/// let archive = SqliteEventArchive::new(archive_pool);
+/// archive.create_table().await?;
+/// ```
+#[derive(Clone)]
+pub struct SqliteEventArchive {
+    pool: SqlitePool,
+}
+
+impl SqliteEventArchive {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Creates the `archived_events` table if it doesn't exist.
+    pub async fn create_table(&self) -> Result<(), CqrsError> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS archived_events (
+                id TEXT PRIMARY KEY,
+                aggregate_type TEXT NOT NULL,
+                aggregate_id TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                global_sequence INTEGER,
+                event TEXT NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(archive_error)?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS archived_events_stream
+             ON archived_events (aggregate_type, aggregate_id, version)",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(archive_error)?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS archived_events_global_sequence
+             ON archived_events (global_sequence)",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(archive_error)?;
+
+        Ok(())
+    }
+}
+
+fn archive_error(error: sqlx::Error) -> CqrsError {
+    CqrsError::EventArchive(error.to_string())
+}
+
+fn rows_to_events(rows: Vec<(String,)>) -> Result<Vec<StoredEvent>, CqrsError> {
+    rows.into_iter()
+        .map(|(event,)| {
+            serde_json::from_str(&event).map_err(|e| {
+                CqrsError::EventArchive(format!("Failed to parse archived event JSON: {e}"))
+            })
+        })
+        .collect()
+}
+
+impl EventArchive for SqliteEventArchive {
+    async fn archive_events(&self, events: &[StoredEvent]) -> Result<(), CqrsError> {
+        let mut tx = self.pool.begin().await.map_err(archive_error)?;
+
+        for event in events {
+            sqlx::query(
+                "INSERT OR IGNORE INTO archived_events
+                 (id, aggregate_type, aggregate_id, version, global_sequence, event)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(&event.id)
+            .bind(&event.aggregate_type)
+            .bind(&event.aggregate_id)
+            .bind(event.version as i64)
+            .bind(event.global_sequence)
+            .bind(serde_json::to_string(event)?)
+            .execute(&mut *tx)
+            .await
+            .map_err(archive_error)?;
+        }
+
+        tx.commit().await.map_err(archive_error)
+    }
+
+    async fn load_archived_events(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+    ) -> Result<Vec<StoredEvent>, CqrsError> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "SELECT event FROM archived_events
+             WHERE aggregate_type = ? AND aggregate_id = ?
+             ORDER BY version ASC",
+        )
+        .bind(aggregate_type)
+        .bind(aggregate_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(archive_error)?;
+
+        rows_to_events(rows)
+    }
+
+    async fn load_archived_events_after(
+        &self,
+        after_sequence: i64,
+        limit: usize,
+    ) -> Result<Vec<StoredEvent>, CqrsError> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "SELECT event FROM archived_events
+             WHERE global_sequence > ?
+             ORDER BY global_sequence ASC
+             LIMIT ?",
+        )
+        .bind(after_sequence)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(archive_error)?;
+
+        rows_to_events(rows)
+    }
+}
@@ -1,6 +1,6 @@
 use std::future::Future;
 
-use crate::{Aggregate, CqrsError};
+use crate::{Aggregate, CqrsError, EventMetadata, ExpectedVersion, StoredEvent};
 
 /// The `Command` trait defines the behavior of a command in a CQRS application.
 ///
@@ -14,6 +14,10 @@ use crate::{Aggregate, CqrsError};
 ///   preconditions such as aggregate/stream mismatches, stale command targets, or invalid
 ///   command routing.
 ///
+/// Commands can also declare whether they create a new aggregate or require an existing one by
+/// overriding [`Command::expected_version`]: the framework then rejects them with
+/// [`CqrsError::Conflict`] or [`CqrsError::AggregateNotFound`] before they are handled.
+///
 /// ## Example
 ///
 /// ```rust,ignore
@@ -38,4 +42,111 @@ pub trait Command: Send + Sync {
         &self,
         aggregate: &Self::Aggregate,
     ) -> impl Future<Output = Result<Vec<<Self::Aggregate as Aggregate>::Event>, CqrsError>> + Send;
+
+    /// The expected state of the aggregate stream. Defaults to [`ExpectedVersion::Any`].
+    ///
+    /// Use [`ExpectedVersion::NoStream`] for commands creating an aggregate and
+    /// [`ExpectedVersion::StreamExists`] for commands that need an existing one.
+    fn expected_version(&self) -> ExpectedVersion {
+        ExpectedVersion::Any
+    }
+
+    /// The metadata (actor, correlation ID, etc.) attached to the events emitted by the
+    /// command. Defaults to empty metadata.
+    fn metadata(&self) -> EventMetadata {
+        EventMetadata::default()
+    }
+}
+
+// The domain events emitted by a `CommandWithReply`, along with its reply.
+type EventsWithReply<C> = (
+    Vec<<<C as CommandWithReply>::Aggregate as Aggregate>::Event>,
+    <C as CommandWithReply>::Reply,
+);
+
+/// The `CommandWithReply` trait defines a command that hands a value back to the caller
+/// (a generated reservation number, a computed price, etc.) alongside its events.
+///
+/// Every `CommandWithReply` is also a [`Command`], so it can be executed with
+/// [`Cqrs::execute`](crate::Cqrs::execute) too, in which case the reply is discarded. Use
+/// [`Cqrs::execute_with_reply`](crate::Cqrs::execute_with_reply) to get it back.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// impl CommandWithReply for CmdBookRoom {
+///     type Aggregate = HotelAggregate;
+///     type Reply = u32;
+///
+///     async fn handle_with_reply(
+///         &self,
+///         aggregate: &Self::Aggregate,
+///     ) -> Result<(Vec<HotelEvent>, u32), CqrsError> {
+///         let room_number = aggregate.first_free_room()?;
+///         Ok((vec![HotelEvent::RoomBooked { room_number }], room_number))
+///     }
+/// }
+/// ```
+pub trait CommandWithReply: Send + Sync {
+    /// The type of aggregate that this command handles.
+    type Aggregate: Aggregate;
+
+    /// The type of value returned to the caller.
+    type Reply: Send;
+
+    /// Handles the command and returns a list of domain events along with the reply.
+    fn handle_with_reply(
+        &self,
+        aggregate: &Self::Aggregate,
+    ) -> impl Future<Output = Result<EventsWithReply<Self>, CqrsError>> + Send;
+
+    /// The expected state of the aggregate stream. See [`Command::expected_version`].
+    fn expected_version(&self) -> ExpectedVersion {
+        ExpectedVersion::Any
+    }
+
+    /// The metadata attached to the events emitted by the command. See [`Command::metadata`].
+    fn metadata(&self) -> EventMetadata {
+        EventMetadata::default()
+    }
+}
+
+impl<T> Command for T
+where
+    T: CommandWithReply,
+{
+    type Aggregate = <T as CommandWithReply>::Aggregate;
+
+    async fn handle(
+        &self,
+        aggregate: &Self::Aggregate,
+    ) -> Result<Vec<<Self::Aggregate as Aggregate>::Event>, CqrsError> {
+        let (events, _reply) = self.handle_with_reply(aggregate).await?;
+        Ok(events)
+    }
+
+    fn expected_version(&self) -> ExpectedVersion {
+        CommandWithReply::expected_version(self)
+    }
+
+    fn metadata(&self) -> EventMetadata {
+        CommandWithReply::metadata(self)
+    }
+}
+
+/// The outcome of a command executed with
+/// [`Cqrs::execute_with_reply`](crate::Cqrs::execute_with_reply).
+#[derive(Clone, Debug)]
+pub struct CommandReply<A, R>
+where
+    A: Aggregate,
+{
+    /// The ID of the aggregate the command was executed on.
+    pub aggregate_id: A::Id,
+    /// The version of the aggregate after the command.
+    pub version: u64,
+    /// The events persisted by the command.
+    pub events: Vec<StoredEvent>,
+    /// The value returned by the command.
+    pub reply: R,
 }
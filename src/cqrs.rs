@@ -1,8 +1,12 @@
+use std::collections::HashMap;
 use std::future::Future;
+use std::time::Instant;
 
 use crate::{
-    query::QueryRunner, Aggregate, AggregateManager, Command, CqrsError, EventConsumers,
-    EventMetadata, EventStore, NewEvent,
+    query::QueryRunner, Aggregate, AggregateManager, Clock, Command, CommandContext,
+    CommandMiddleware, CommandMiddlewares, CommandReply, CommandWithReply, CqrsError,
+    EventConsumers, EventStore, ExpectedVersion, IdGenerator, NewEvent, StoredEvent, SystemClock,
+    TenantEventStore, UuidGenerator,
 };
 
 /// The `Cqrs` trait represents the main entry point of a CQRS application.
@@ -20,6 +24,77 @@ pub trait Cqrs: QueryRunner + Send + Sync {
     ) -> impl Future<Output = Result<<C::Aggregate as Aggregate>::Id, CqrsError>> + Send
     where
         C: Command;
+
+    /// Executes a command that hands a value back to the caller, returning it together with
+    /// the persisted outcome.
+    fn execute_with_reply<C>(
+        &self,
+        aggregate_id: &<C::Aggregate as Aggregate>::Id,
+        command: &C,
+    ) -> impl Future<Output = Result<CommandReply<C::Aggregate, C::Reply>, CqrsError>> + Send
+    where
+        C: CommandWithReply;
+
+    /// Executes a batch of commands, returning the outcome of each command in order.
+    ///
+    /// Commands are grouped by aggregate: each aggregate is loaded once, its commands are
+    /// handled in order (each one seeing the events emitted by the previous ones), and all the
+    /// resulting events are appended in a single `save_events` call per aggregate. A command
+    /// failing doesn't prevent the following ones from running, but if an aggregate can't be
+    /// loaded or an append fails, every command that targeted it or emitted events for it
+    /// fails: the first one with the error, the others with a copy of it (see
+    /// [`BatchOutcome::results`]).
+    ///
+    /// Once the events of an aggregate are saved, its commands succeed. Failures occurring
+    /// afterwards are reported once per aggregate in [`BatchOutcome::post_commit_errors`].
+    fn execute_batch<C>(
+        &self,
+        commands: &[(<C::Aggregate as Aggregate>::Id, C)],
+    ) -> impl Future<Output = BatchOutcome<C::Aggregate>> + Send
+    where
+        C: Command;
+
+    /// Previews the outcome of a command without persisting anything: the command is handled
+    /// against the current state of the aggregate, and the resulting events are applied to it
+    /// but never saved, dispatched to consumers, or stored by the aggregate manager.
+    ///
+    /// Middlewares see the command as a dry run (see [`CommandContext::dry_run`]).
+    fn simulate<C>(
+        &self,
+        aggregate_id: &<C::Aggregate as Aggregate>::Id,
+        command: &C,
+    ) -> impl Future<Output = Result<Simulation<C::Aggregate>, CqrsError>> + Send
+    where
+        C: Command;
+}
+
+/// The would-be outcome of a command previewed with [`Cqrs::simulate`].
+#[derive(Clone, Debug)]
+pub struct Simulation<A>
+where
+    A: Aggregate,
+{
+    /// The events the command would emit.
+    pub events: Vec<NewEvent>,
+    /// The state of the aggregate after applying the events.
+    pub aggregate: A,
+}
+
+/// The outcome of a batch executed with [`Cqrs::execute_batch`].
+#[derive(Debug)]
+pub struct BatchOutcome<A>
+where
+    A: Aggregate,
+{
+    /// The outcome of each command, in order. A command succeeds once its events are saved.
+    ///
+    /// Errors shared by several commands are copied, and copies hold the message of the
+    /// sources that can't be cloned (e.g. [`CqrsError::DomainSource`]) instead of the sources.
+    pub results: Vec<Result<A::Id, CqrsError>>,
+    /// The failures that occurred after the events of an aggregate were saved, while
+    /// dispatching them to consumers, storing the aggregate or running the `after_commit`
+    /// hook of the middlewares. The events are committed: the commands must not be retried.
+    pub post_commit_errors: Vec<(A::Id, CqrsError)>,
 }
 
 /// A synchronous-consumer implementation of the [`Cqrs`] trait.
@@ -29,14 +104,32 @@ pub trait Cqrs: QueryRunner + Send + Sync {
 ///
 /// The execution flow:
 /// 1. Load aggregate from the aggregate manager
-/// 2. Execute the command, getting domain events or a semantic error
+/// 2. Run the `before_handle` hook of the middlewares and check the command's expected version
+/// 3. Execute the command, getting domain events or a semantic error
 ///    (`Domain` for business rules, `CommandInvariant` for application preconditions)
-/// 3. Wrap domain events into `NewEvent` structs
-/// 4. Save events to the event store (with optimistic concurrency check)
-/// 5. Apply events to the aggregate
-/// 6. Process events through consumers
-/// 7. Store the aggregate (e.g., snapshot)
-/// 8. Return the aggregate ID
+/// 4. Apply domain events to a copy of the aggregate and check its invariants, rejecting the
+///    command with `CommandInvariant` if the resulting state is invalid
+/// 5. Wrap domain events into `NewEvent` structs and run the `after_handle` hook
+/// 6. Save events to the event store (with optimistic concurrency check)
+/// 7. Replace the aggregate with its updated copy
+/// 8. Process events through consumers
+/// 9. Store the aggregate (e.g., snapshot)
+/// 10. Run the `after_commit` hook of the middlewares
+/// 11. Return the aggregate ID (and the command's reply with [`Cqrs::execute_with_reply`])
+///
+/// [`Cqrs::execute_batch`] runs steps 2 to 5 for each command of an aggregate, handling each
+/// command against the state left by the previous ones, then runs the remaining steps once per
+/// aggregate; the `after_commit` hook still runs once per command. A failure after step 6
+/// stops the remaining steps for that aggregate, like with [`Cqrs::execute`], but doesn't fail
+/// its commands.
+///
+/// [`Cqrs::simulate`] follows the same flow up to step 5, with [`CommandContext::dry_run`] set
+/// for the middlewares, then returns the updated copy of the aggregate without saving the
+/// events; the `after_commit` hook isn't run.
+///
+/// An instance bound to a tenant with [`SimpleCqrs::with_tenant`] saves events through an
+/// event store scoped to the tenant, and checks that the stream targeted by a command belongs
+/// to the tenant before loading the aggregate, whichever store the aggregate manager reads.
 pub struct SimpleCqrs<ES, AM>
 where
     AM: AggregateManager,
@@ -45,6 +138,10 @@ where
     aggregate_manager: AM,
     event_store: ES,
     consumers: EventConsumers,
+    middlewares: CommandMiddlewares,
+    clock: Box<dyn Clock>,
+    id_generator: Box<dyn IdGenerator>,
+    tenant_id: Option<String>,
 }
 
 impl<ES, AM> SimpleCqrs<ES, AM>
@@ -58,60 +155,497 @@ where
             aggregate_manager,
             event_store,
             consumers,
+            middlewares: CommandMiddlewares::new(),
+            clock: Box::new(SystemClock),
+            id_generator: Box::new(UuidGenerator),
+            tenant_id: None,
         }
     }
+
+    /// Sets the clock timestamping new events. Defaults to [`SystemClock`].
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Sets the generator of the IDs of new events. Defaults to [`UuidGenerator`].
+    pub fn with_id_generator(mut self, id_generator: impl IdGenerator + 'static) -> Self {
+        self.id_generator = Box::new(id_generator);
+        self
+    }
+
+    /// Returns the clock timestamping new events, for application logic that must agree with
+    /// it (e.g. scheduled commands).
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Adds a middleware around command execution. Middlewares run in the order they are added.
+    pub fn with_middleware(mut self, middleware: impl CommandMiddleware + 'static) -> Self {
+        self.middlewares = self.middlewares.with(middleware);
+        self
+    }
 }
 
-impl<ES, AM> Cqrs for SimpleCqrs<ES, AM>
+impl<ES, AM> SimpleCqrs<ES, AM>
+where
+    AM: AggregateManager,
+    ES: TenantEventStore,
+{
+    /// Binds the instance to a tenant: commands run on its behalf, with the tenant recorded in
+    /// the metadata of their events before the middlewares run, and the event store is scoped
+    /// to it with [`TenantEventStore::for_tenant`]. Commands targeting an aggregate of another
+    /// tenant, or whose metadata names another tenant, fail with
+    /// [`CqrsError::TenantMismatch`].
+    pub fn with_tenant(mut self, tenant_id: impl Into<String>) -> Self {
+        let tenant_id = tenant_id.into();
+        self.event_store = self.event_store.for_tenant(&tenant_id);
+        self.tenant_id = Some(tenant_id);
+        self
+    }
+}
+
+// Applies the events emitted by a command to a copy of the aggregate, and checks the invariants
+// of the resulting state.
+async fn evolve<A>(aggregate: &A, domain_events: &[A::Event]) -> Result<A, CqrsError>
+where
+    A: Aggregate,
+{
+    let mut updated = aggregate.clone();
+    for event in domain_events.iter() {
+        updated.apply(event).await?;
+    }
+
+    updated
+        .check_invariants()
+        .map_err(CqrsError::into_invariant)?;
+
+    Ok(updated)
+}
+
+// Reports an error to `count` commands of a batch: the first one gets the error, the others a
+// copy of it.
+fn share_error(error: CqrsError, count: usize) -> Vec<Result<(), CqrsError>> {
+    let mut errors: Vec<_> = (1..count).map(|_| Err(error.duplicate())).collect();
+    errors.insert(0, Err(error));
+    errors
+}
+
+// A command of a batch whose events are waiting to be committed with the rest of its stream.
+struct StagedCommand {
+    index: usize,
+    context: CommandContext,
+    event_count: usize,
+}
+
+impl<ES, AM> SimpleCqrs<ES, AM>
 where
     AM: AggregateManager,
     ES: EventStore,
 {
-    async fn execute<C>(
+    // Loads an aggregate, checking first that its stream belongs to the tenant of the instance
+    // through the scoped event store, as the aggregate manager may read from any store.
+    async fn load<A>(&self, aggregate_id: &A::Id) -> Result<A, CqrsError>
+    where
+        A: Aggregate,
+    {
+        if self.tenant_id.is_some() {
+            self.event_store
+                .stream_version(std::any::type_name::<A>(), &aggregate_id.to_string())
+                .await
+                .map_err(CqrsError::into_event_store)?;
+        }
+
+        self.aggregate_manager.load::<A>(aggregate_id).await
+    }
+
+    // Loads the aggregate targeted by a command and runs the checks preceding its handling.
+    async fn prepare<C>(
         &self,
         aggregate_id: &<C::Aggregate as Aggregate>::Id,
         command: &C,
-    ) -> Result<<C::Aggregate as Aggregate>::Id, CqrsError>
+    ) -> Result<(C::Aggregate, CommandContext), CqrsError>
     where
         C: Command,
     {
-        let mut aggregate = self
-            .aggregate_manager
-            .load::<C::Aggregate>(aggregate_id)
-            .await?;
+        let aggregate = self.load::<C::Aggregate>(aggregate_id).await?;
 
-        let domain_events = command.handle(&aggregate).await?;
+        let context = self.begin(&aggregate, aggregate_id, command, false).await?;
 
-        let current_version = aggregate.version();
+        Ok((aggregate, context))
+    }
+
+    // Runs the checks preceding the handling of a command by an already loaded aggregate.
+    async fn begin<C>(
+        &self,
+        aggregate: &C::Aggregate,
+        aggregate_id: &<C::Aggregate as Aggregate>::Id,
+        command: &C,
+        dry_run: bool,
+    ) -> Result<CommandContext, CqrsError>
+    where
+        C: Command,
+    {
+        let mut context = CommandContext {
+            command_name: std::any::type_name::<C>(),
+            aggregate_type: std::any::type_name::<C::Aggregate>(),
+            aggregate_id: aggregate_id.to_string(),
+            metadata: command.metadata(),
+            started_at: Instant::now(),
+            dry_run,
+        };
+
+        if let Some(tenant_id) = &self.tenant_id {
+            if context
+                .metadata
+                .tenant_id
+                .as_ref()
+                .is_some_and(|other| other != tenant_id)
+            {
+                return Err(CqrsError::TenantMismatch {
+                    aggregate_id: context.aggregate_id,
+                    tenant_id: tenant_id.clone(),
+                });
+            }
+            context.metadata.tenant_id = Some(tenant_id.clone());
+        }
+
+        self.middlewares.before_handle(&mut context).await?;
+
+        command
+            .expected_version()
+            .check(&context.aggregate_id, aggregate.version())?;
+
+        Ok(context)
+    }
+
+    // Wraps the events emitted by a command and runs the checks preceding their persistence.
+    async fn wrap_events<A>(
+        &self,
+        context: &CommandContext,
+        domain_events: Vec<A::Event>,
+    ) -> Result<Vec<NewEvent>, CqrsError>
+    where
+        A: Aggregate,
+    {
         let new_events: Vec<NewEvent> = domain_events
             .into_iter()
-            .map(|payload| NewEvent::from_payload(payload, EventMetadata::default()))
+            .map(|payload| {
+                NewEvent::from_payload_with(
+                    payload,
+                    context.metadata.clone(),
+                    self.clock.as_ref(),
+                    self.id_generator.as_ref(),
+                )
+            })
             .collect::<Result<Vec<_>, _>>()?;
 
+        self.middlewares.after_handle(context, &new_events).await?;
+
+        Ok(new_events)
+    }
+
+    // Persists the events emitted by a command, then applies and dispatches them.
+    async fn commit<A>(
+        &self,
+        aggregate: &mut A,
+        context: &CommandContext,
+        domain_events: Vec<A::Event>,
+    ) -> Result<Vec<StoredEvent>, CqrsError>
+    where
+        A: Aggregate,
+    {
+        let current_version = aggregate.version();
+        let mut updated = evolve(aggregate, &domain_events).await?;
+        let new_events = self.wrap_events::<A>(context, domain_events).await?;
+
         let events = self
             .event_store
             .save_events(
-                std::any::type_name::<C::Aggregate>(),
-                &aggregate_id.to_string(),
+                context.aggregate_type,
+                &context.aggregate_id,
                 &new_events,
-                current_version,
+                ExpectedVersion::Exact(current_version),
             )
             .await?;
 
-        aggregate.apply_events(&events).await?;
         let new_version = events.last().map(|e| e.version).unwrap_or(current_version);
-        aggregate.set_version(new_version);
+        updated.set_version(new_version);
+        *aggregate = updated;
 
         for event in events.iter() {
             self.consumers.process(event).await?;
         }
 
-        self.aggregate_manager
-            .store::<C::Aggregate>(&aggregate)
+        self.aggregate_manager.store::<A>(aggregate).await?;
+
+        self.middlewares.after_commit(context, &events).await?;
+
+        Ok(events)
+    }
+
+    // Handles the commands of a batch targeting the same aggregate, then commits all their
+    // events at once. Returns the outcome of each command in order, and the failure that
+    // occurred after the events were saved, if any.
+    async fn execute_stream<C>(
+        &self,
+        aggregate_id: &<C::Aggregate as Aggregate>::Id,
+        commands: &[&C],
+    ) -> (Vec<Result<(), CqrsError>>, Option<CqrsError>)
+    where
+        C: Command,
+    {
+        let mut aggregate = match self.load::<C::Aggregate>(aggregate_id).await {
+            Ok(aggregate) => aggregate,
+            Err(error) => return (share_error(error, commands.len()), None),
+        };
+        let loaded_version = aggregate.version();
+
+        let mut results = Vec::with_capacity(commands.len());
+        let mut staged = Vec::new();
+        let mut new_events = Vec::new();
+
+        for command in commands {
+            match self.stage(&mut aggregate, aggregate_id, *command, false).await {
+                Ok((context, events)) => {
+                    staged.push(StagedCommand {
+                        index: results.len(),
+                        context,
+                        event_count: events.len(),
+                    });
+                    new_events.extend(events);
+                    results.push(Ok(()));
+                }
+                Err(error) => results.push(Err(error)),
+            }
+        }
+
+        if new_events.is_empty() {
+            return (results, None);
+        }
+
+        let events = match self
+            .save_stream(&mut aggregate, loaded_version, &staged, &new_events)
+            .await
+        {
+            Ok(events) => events,
+            Err(error) => {
+                let errors = share_error(error, staged.len());
+                for (command, error) in staged.iter().zip(errors) {
+                    results[command.index] = error;
+                }
+                return (results, None);
+            }
+        };
+
+        let post_commit_error = self
+            .dispatch_stream(&aggregate, &staged, &events)
+            .await
+            .err();
+
+        (results, post_commit_error)
+    }
+
+    // Handles a command and applies its events to the aggregate, without saving them.
+    async fn stage<C>(
+        &self,
+        aggregate: &mut C::Aggregate,
+        aggregate_id: &<C::Aggregate as Aggregate>::Id,
+        command: &C,
+        dry_run: bool,
+    ) -> Result<(CommandContext, Vec<NewEvent>), CqrsError>
+    where
+        C: Command,
+    {
+        let context = self.begin(aggregate, aggregate_id, command, dry_run).await?;
+
+        let domain_events = command.handle(aggregate).await?;
+
+        let mut updated = evolve(aggregate, &domain_events).await?;
+
+        let events = self
+            .wrap_events::<C::Aggregate>(&context, domain_events)
             .await?;
 
+        updated.set_version(aggregate.version() + events.len() as u64);
+        *aggregate = updated;
+
+        Ok((context, events))
+    }
+
+    // Persists the events staged for an aggregate by a batch.
+    async fn save_stream<A>(
+        &self,
+        aggregate: &mut A,
+        loaded_version: u64,
+        staged: &[StagedCommand],
+        new_events: &[NewEvent],
+    ) -> Result<Vec<StoredEvent>, CqrsError>
+    where
+        A: Aggregate,
+    {
+        let Some(StagedCommand { context, .. }) = staged.first() else {
+            return Ok(vec![]);
+        };
+
+        let events = self
+            .event_store
+            .save_events(
+                context.aggregate_type,
+                &context.aggregate_id,
+                new_events,
+                ExpectedVersion::Exact(loaded_version),
+            )
+            .await?;
+
+        if let Some(event) = events.last() {
+            aggregate.set_version(event.version);
+        }
+
+        Ok(events)
+    }
+
+    // Dispatches the events saved for an aggregate by a batch, and stores the aggregate.
+    async fn dispatch_stream<A>(
+        &self,
+        aggregate: &A,
+        staged: &[StagedCommand],
+        events: &[StoredEvent],
+    ) -> Result<(), CqrsError>
+    where
+        A: Aggregate,
+    {
+        for event in events.iter() {
+            self.consumers.process(event).await?;
+        }
+
+        self.aggregate_manager.store::<A>(aggregate).await?;
+
+        let mut remaining = events;
+        for command in staged {
+            let (command_events, rest) =
+                remaining.split_at(command.event_count.min(remaining.len()));
+            self.middlewares
+                .after_commit(&command.context, command_events)
+                .await?;
+            remaining = rest;
+        }
+
+        Ok(())
+    }
+}
+
+impl<ES, AM> Cqrs for SimpleCqrs<ES, AM>
+where
+    AM: AggregateManager,
+    ES: EventStore,
+{
+    async fn execute<C>(
+        &self,
+        aggregate_id: &<C::Aggregate as Aggregate>::Id,
+        command: &C,
+    ) -> Result<<C::Aggregate as Aggregate>::Id, CqrsError>
+    where
+        C: Command,
+    {
+        let (mut aggregate, context) = self.prepare(aggregate_id, command).await?;
+
+        let domain_events = command.handle(&aggregate).await?;
+
+        self.commit(&mut aggregate, &context, domain_events).await?;
+
         Ok(aggregate_id.clone())
     }
+
+    async fn execute_with_reply<C>(
+        &self,
+        aggregate_id: &<C::Aggregate as Aggregate>::Id,
+        command: &C,
+    ) -> Result<CommandReply<C::Aggregate, C::Reply>, CqrsError>
+    where
+        C: CommandWithReply,
+    {
+        let (mut aggregate, context) = self.prepare(aggregate_id, command).await?;
+
+        let (domain_events, reply) = command.handle_with_reply(&aggregate).await?;
+
+        let events = self.commit(&mut aggregate, &context, domain_events).await?;
+
+        Ok(CommandReply {
+            aggregate_id: aggregate_id.clone(),
+            version: aggregate.version(),
+            events,
+            reply,
+        })
+    }
+
+    async fn execute_batch<C>(
+        &self,
+        commands: &[(<C::Aggregate as Aggregate>::Id, C)],
+    ) -> BatchOutcome<C::Aggregate>
+    where
+        C: Command,
+    {
+        // Groups the commands by aggregate, keeping the order of first appearance.
+        let mut streams: Vec<(&<C::Aggregate as Aggregate>::Id, Vec<usize>)> = Vec::new();
+        let mut positions = HashMap::new();
+        for (index, (aggregate_id, _)) in commands.iter().enumerate() {
+            let position = *positions.entry(aggregate_id).or_insert_with(|| {
+                streams.push((aggregate_id, Vec::new()));
+                streams.len() - 1
+            });
+            streams[position].1.push(index);
+        }
+
+        let mut outcomes: Vec<Option<Result<(), CqrsError>>> =
+            commands.iter().map(|_| None).collect();
+        let mut post_commit_errors = Vec::new();
+
+        for (aggregate_id, indices) in streams {
+            let stream_commands: Vec<&C> = indices.iter().map(|&i| &commands[i].1).collect();
+            let (results, post_commit_error) =
+                self.execute_stream(aggregate_id, &stream_commands).await;
+
+            for (index, result) in indices.into_iter().zip(results) {
+                outcomes[index] = Some(result);
+            }
+            if let Some(error) = post_commit_error {
+                post_commit_errors.push((aggregate_id.clone(), error));
+            }
+        }
+
+        let results = outcomes
+            .into_iter()
+            .zip(commands)
+            .map(|(outcome, (aggregate_id, _))| {
+                outcome
+                    .expect("every command belongs to a stream")
+                    .map(|()| aggregate_id.clone())
+            })
+            .collect();
+
+        BatchOutcome {
+            results,
+            post_commit_errors,
+        }
+    }
+
+    async fn simulate<C>(
+        &self,
+        aggregate_id: &<C::Aggregate as Aggregate>::Id,
+        command: &C,
+    ) -> Result<Simulation<C::Aggregate>, CqrsError>
+    where
+        C: Command,
+    {
+        let mut aggregate = self.load::<C::Aggregate>(aggregate_id).await?;
+
+        let (_, events) = self
+            .stage(&mut aggregate, aggregate_id, command, true)
+            .await?;
+
+        Ok(Simulation { events, aggregate })
+    }
 }
 
 /// Implements `QueryRunner` so you can call `cqrs.query(&q).await`.
@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::{KeyStore, SubjectKey};
+use crate::CqrsError;
+
+/// A [`KeyStore`] keeping keys in memory, for tests and prototypes. Clones share the same
+/// keys.
+///
+/// ```rust,ignore
+/// let event_store = EncryptedEventStore::new(InMemoryEventStore::new(), InMemoryKeyStore::new());
+/// ```
+#[derive(Clone, Default)]
+pub struct InMemoryKeyStore {
+    keys: Arc<Mutex<HashMap<String, SubjectKey>>>,
+}
+
+impl InMemoryKeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KeyStore for InMemoryKeyStore {
+    async fn load_key(&self, subject: &str) -> Result<Option<SubjectKey>, CqrsError> {
+        Ok(self.keys.lock().unwrap().get(subject).cloned())
+    }
+
+    async fn load_or_create_key(&self, subject: &str) -> Result<SubjectKey, CqrsError> {
+        let mut keys = self.keys.lock().unwrap();
+        Ok(keys
+            .entry(subject.to_string())
+            .or_insert_with(SubjectKey::generate)
+            .clone())
+    }
+
+    async fn delete_key(&self, subject: &str) -> Result<bool, CqrsError> {
+        Ok(self.keys.lock().unwrap().remove(subject).is_some())
+    }
+}
@@ -0,0 +1,450 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    CqrsError, EventStore, ExpectedVersion, NewEvent, StoredEvent, TenantEventStore, UnitOfWork,
+};
+
+mod memory;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use memory::InMemoryKeyStore;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteKeyStore;
+
+/// The key encrypting the personal data of a data subject, for AES-256-GCM.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SubjectKey([u8; 32]);
+
+impl SubjectKey {
+    /// Generates a random key.
+    pub fn generate() -> Self {
+        Self(Aes256Gcm::generate_key(OsRng).into())
+    }
+
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+// Keys never show up in logs.
+impl fmt::Debug for SubjectKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SubjectKey(..)")
+    }
+}
+
+/// The `KeyStore` trait defines the storage of the keys encrypting the personal data of each
+/// data subject.
+///
+/// Deleting the key of a subject makes their personal data unreadable in every event, which
+/// erases it without rewriting the event log (crypto-shredding). Keys must therefore be kept
+/// apart from the events and their backups.
+pub trait KeyStore: Send + Sync {
+    /// Loads the key of a data subject, `None` if the subject has no key or was forgotten.
+    fn load_key(
+        &self,
+        subject: &str,
+    ) -> impl Future<Output = Result<Option<SubjectKey>, CqrsError>> + Send;
+
+    /// Loads the key of a data subject, generating one with [`SubjectKey::generate`] if the
+    /// subject has none. Concurrent calls for the same subject must return the same key.
+    fn load_or_create_key(
+        &self,
+        subject: &str,
+    ) -> impl Future<Output = Result<SubjectKey, CqrsError>> + Send;
+
+    /// Deletes the key of a data subject. Returns whether the subject had a key.
+    fn delete_key(&self, subject: &str) -> impl Future<Output = Result<bool, CqrsError>> + Send;
+}
+
+/// An [`EventStore`] decorator encrypting the personal data of events, available with the
+/// `encryption` feature.
+///
+/// The fields listed by [`EventPayload::personal_data`] are encrypted with the key of their
+/// subject before being saved, and decrypted when events are loaded, so that
+/// [`StoredEvent::get_payload`] returns them as they were. Once a subject is forgotten with
+/// [`EncryptedEventStore::forget_subject`], their fields can't be decrypted anymore and are
+/// left out of the payloads, taking their `#[serde(default)]` value.
+///
+/// Events read without the decorator (outbox, archive) keep their personal data encrypted.
+/// Snapshots and read models are not covered: rebuild the ones holding the personal data of a
+/// forgotten subject. A subject that is forgotten and then referenced by new events gets a new
+/// key, which doesn't unlock the previous events.
+///
+/// Wrapping a [`TenantEventStore`] makes a tenant-aware store too, whose scoped stores share
+/// the key store.
+///
+/// ```rust,ignore
+/// let event_store = EncryptedEventStore::new(SqliteEventStore::new(pool.clone()), key_store);
+///
+/// // Later, on an erasure request.
+/// event_store.forget_subject(&guest_id).await?;
+/// ```
+///
+/// [`EventPayload::personal_data`]: crate::EventPayload::personal_data
+#[derive(Clone)]
+pub struct EncryptedEventStore<ES, KS>
+where
+    ES: EventStore,
+    KS: KeyStore,
+{
+    inner: ES,
+    key_store: KS,
+}
+
+impl<ES, KS> EncryptedEventStore<ES, KS>
+where
+    ES: EventStore,
+    KS: KeyStore,
+{
+    pub fn new(inner: ES, key_store: KS) -> Self {
+        Self { inner, key_store }
+    }
+
+    /// Deletes the key of a data subject, making their personal data unreadable in every
+    /// event. Returns whether the subject had a key.
+    pub async fn forget_subject(&self, subject: &str) -> Result<bool, CqrsError> {
+        self.key_store
+            .delete_key(subject)
+            .await
+            .map_err(CqrsError::into_key_store)
+    }
+
+    async fn encrypt_events(&self, events: &[NewEvent]) -> Result<Vec<NewEvent>, CqrsError> {
+        let mut encrypted = Vec::with_capacity(events.len());
+        for event in events {
+            let mut event = event.clone();
+            if let Some(personal_data) = &event.personal_data {
+                let key = self
+                    .key_store
+                    .load_or_create_key(&personal_data.subject)
+                    .await
+                    .map_err(CqrsError::into_key_store)?;
+
+                for pointer in &personal_data.fields {
+                    let field = event.payload.pointer_mut(pointer).ok_or_else(|| {
+                        CqrsError::EventStore(format!(
+                            "personal data field `{pointer}` not found in {} payload",
+                            event.event_type
+                        ))
+                    })?;
+                    *field = encrypt(&key, &personal_data.subject, field)?;
+                }
+            }
+            encrypted.push(event);
+        }
+        Ok(encrypted)
+    }
+
+    async fn decrypt_events(
+        &self,
+        mut events: Vec<StoredEvent>,
+    ) -> Result<Vec<StoredEvent>, CqrsError> {
+        // Events of the same subjects are often loaded together.
+        let mut keys = HashMap::new();
+        for event in &mut events {
+            decrypt_fields(&self.key_store, &mut keys, &mut event.payload).await?;
+        }
+        Ok(events)
+    }
+}
+
+// The key of the object replacing an encrypted field in a payload.
+const ENCRYPTED_FIELD: &str = "$encrypted";
+
+// Returns `true` if `value` is an encrypted field.
+fn is_encrypted(value: &serde_json::Value) -> bool {
+    value
+        .as_object()
+        .is_some_and(|object| object.len() == 1 && object.contains_key(ENCRYPTED_FIELD))
+}
+
+// An encrypted field, replacing the field value in the payload.
+#[derive(Serialize, Deserialize)]
+struct EncryptedField {
+    subject: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+fn encrypt(
+    key: &SubjectKey,
+    subject: &str,
+    value: &serde_json::Value,
+) -> Result<serde_json::Value, CqrsError> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_bytes()));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let plaintext = serde_json::to_vec(value)?;
+
+    // The subject is authenticated, so that a field can't be moved to another subject.
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: &plaintext,
+                aad: subject.as_bytes(),
+            },
+        )
+        .map_err(|_| CqrsError::EventStore("failed to encrypt personal data".to_string()))?;
+
+    let field = EncryptedField {
+        subject: subject.to_string(),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+    Ok(serde_json::json!({ ENCRYPTED_FIELD: field }))
+}
+
+fn decrypt(key: &SubjectKey, field: &EncryptedField) -> Result<serde_json::Value, CqrsError> {
+    let undecryptable = || {
+        CqrsError::EventStore(format!(
+            "failed to decrypt personal data of subject {}",
+            field.subject
+        ))
+    };
+
+    let nonce = BASE64.decode(&field.nonce).map_err(|_| undecryptable())?;
+    let ciphertext = BASE64
+        .decode(&field.ciphertext)
+        .map_err(|_| undecryptable())?;
+    if nonce.len() != 12 {
+        return Err(undecryptable());
+    }
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_bytes()));
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: field.subject.as_bytes(),
+            },
+        )
+        .map_err(|_| undecryptable())?;
+
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+// Decrypts the encrypted fields found in `value`. The fields of forgotten subjects are removed
+// from their object, so that they take their default value when the payload is deserialized.
+async fn decrypt_fields<KS: KeyStore>(
+    key_store: &KS,
+    keys: &mut HashMap<String, Option<SubjectKey>>,
+    value: &mut serde_json::Value,
+) -> Result<(), CqrsError> {
+    if is_encrypted(value) {
+        decrypt_field(key_store, keys, value).await?;
+        return Ok(());
+    }
+
+    let mut pending = vec![value];
+    while let Some(value) = pending.pop() {
+        match value {
+            serde_json::Value::Object(object) => {
+                let mut forgotten = Vec::new();
+                for (name, field) in object.iter_mut() {
+                    if is_encrypted(field) && !decrypt_field(key_store, keys, field).await? {
+                        forgotten.push(name.clone());
+                    }
+                }
+                for name in forgotten {
+                    object.remove(&name);
+                }
+                pending.extend(object.values_mut());
+            }
+            serde_json::Value::Array(items) => {
+                for item in items.iter_mut() {
+                    if is_encrypted(item) {
+                        decrypt_field(key_store, keys, item).await?;
+                    }
+                }
+                pending.extend(items.iter_mut());
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Decrypts an encrypted field in place. Returns `false`, leaving the field as it is, if its
+// subject was forgotten.
+async fn decrypt_field<KS: KeyStore>(
+    key_store: &KS,
+    keys: &mut HashMap<String, Option<SubjectKey>>,
+    value: &mut serde_json::Value,
+) -> Result<bool, CqrsError> {
+    let field: EncryptedField = serde_json::from_value(value[ENCRYPTED_FIELD].clone())
+        .map_err(|e| CqrsError::EventStore(format!("invalid encrypted field: {e}")))?;
+
+    if !keys.contains_key(&field.subject) {
+        let key = key_store
+            .load_key(&field.subject)
+            .await
+            .map_err(CqrsError::into_key_store)?;
+        keys.insert(field.subject.clone(), key);
+    }
+    match &keys[&field.subject] {
+        Some(key) => {
+            *value = decrypt(key, &field)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+// Scoping to a tenant is delegated to the wrapped store, and the scoped store shares the key
+// store, so that keys remain per subject across tenants.
+impl<ES, KS> TenantEventStore for EncryptedEventStore<ES, KS>
+where
+    ES: TenantEventStore,
+    KS: KeyStore + Clone,
+{
+    fn for_tenant(&self, tenant_id: &str) -> Self {
+        Self::new(self.inner.for_tenant(tenant_id), self.key_store.clone())
+    }
+
+    fn tenant_id(&self) -> Option<&str> {
+        self.inner.tenant_id()
+    }
+
+    fn check_tenant(&self, aggregate_id: &str, tenant_id: Option<&str>) -> Result<(), CqrsError> {
+        self.inner.check_tenant(aggregate_id, tenant_id)
+    }
+
+    fn assign_tenant(
+        &self,
+        aggregate_id: &str,
+        events: &[NewEvent],
+    ) -> Result<Vec<NewEvent>, CqrsError> {
+        self.inner.assign_tenant(aggregate_id, events)
+    }
+}
+
+impl<ES, KS> EventStore for EncryptedEventStore<ES, KS>
+where
+    ES: EventStore,
+    KS: KeyStore,
+{
+    async fn save_events(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+        events: &[NewEvent],
+        expected_version: ExpectedVersion,
+    ) -> Result<Vec<StoredEvent>, CqrsError> {
+        let events = self.encrypt_events(events).await?;
+        let stored = self
+            .inner
+            .save_events(aggregate_type, aggregate_id, &events, expected_version)
+            .await?;
+        self.decrypt_events(stored).await
+    }
+
+    async fn save_unit_of_work(
+        &self,
+        unit: &UnitOfWork,
+    ) -> Result<Vec<Vec<StoredEvent>>, CqrsError> {
+        let mut encrypted = UnitOfWork::new();
+        for append in unit.appends() {
+            encrypted = encrypted.append(
+                append.aggregate_type.clone(),
+                append.aggregate_id.clone(),
+                self.encrypt_events(&append.events).await?,
+                append.expected_version,
+            );
+        }
+
+        let mut persisted = Vec::new();
+        for stored in self.inner.save_unit_of_work(&encrypted).await? {
+            persisted.push(self.decrypt_events(stored).await?);
+        }
+        Ok(persisted)
+    }
+
+    async fn load_events(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+    ) -> Result<(Vec<StoredEvent>, u64), CqrsError> {
+        let (events, version) = self.inner.load_events(aggregate_type, aggregate_id).await?;
+        Ok((self.decrypt_events(events).await?, version))
+    }
+
+    async fn stream_version(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+    ) -> Result<u64, CqrsError> {
+        self.inner
+            .stream_version(aggregate_type, aggregate_id)
+            .await
+    }
+
+    async fn stream_ids(&self, aggregate_type: &str) -> Result<Vec<String>, CqrsError> {
+        self.inner.stream_ids(aggregate_type).await
+    }
+
+    async fn delete_events_before(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+        version: u64,
+    ) -> Result<usize, CqrsError> {
+        self.inner
+            .delete_events_before(aggregate_type, aggregate_id, version)
+            .await
+    }
+
+    async fn load_events_to_version(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+        version: u64,
+    ) -> Result<(Vec<StoredEvent>, u64), CqrsError> {
+        let (events, version) = self
+            .inner
+            .load_events_to_version(aggregate_type, aggregate_id, version)
+            .await?;
+        Ok((self.decrypt_events(events).await?, version))
+    }
+
+    async fn load_events_after(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+        version: u64,
+    ) -> Result<(Vec<StoredEvent>, u64), CqrsError> {
+        let (events, version) = self
+            .inner
+            .load_events_after(aggregate_type, aggregate_id, version)
+            .await?;
+        Ok((self.decrypt_events(events).await?, version))
+    }
+
+    async fn load_events_as_of(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(Vec<StoredEvent>, u64), CqrsError> {
+        let (events, version) = self
+            .inner
+            .load_events_as_of(aggregate_type, aggregate_id, timestamp)
+            .await?;
+        Ok((self.decrypt_events(events).await?, version))
+    }
+}
@@ -0,0 +1,91 @@
+use sqlx::SqlitePool;
+
+use super::{KeyStore, SubjectKey};
+use crate::CqrsError;
+
+/// A [`KeyStore`] backed by SQLite via sqlx, available with the `encryption` and `sqlite`
+/// features.
+///
+/// Keys are kept in a `subject_keys` table. Give it its own database rather than the one of
+/// the event store, so that backups of the events don't carry the keys of forgotten subjects.
+///
+/// ```rust,ignore
+/// let key_store = SqliteKeyStore::new(keys_pool);
+/// key_store.create_table().await?;
+/// ```
+#[derive(Clone)]
+pub struct SqliteKeyStore {
+    pool: SqlitePool,
+}
+
+impl SqliteKeyStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Creates the `subject_keys` table if it doesn't exist.
+    pub async fn create_table(&self) -> Result<(), CqrsError> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS subject_keys (
+                subject TEXT PRIMARY KEY,
+                key BLOB NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(key_store_error)?;
+
+        Ok(())
+    }
+}
+
+fn key_store_error(error: sqlx::Error) -> CqrsError {
+    CqrsError::KeyStore(error.to_string())
+}
+
+fn restore(subject: &str, key: Vec<u8>) -> Result<SubjectKey, CqrsError> {
+    let bytes = key
+        .try_into()
+        .map_err(|_| CqrsError::KeyStore(format!("invalid key for subject {subject}")))?;
+    Ok(SubjectKey::from_bytes(bytes))
+}
+
+impl KeyStore for SqliteKeyStore {
+    async fn load_key(&self, subject: &str) -> Result<Option<SubjectKey>, CqrsError> {
+        let row: Option<(Vec<u8>,)> =
+            sqlx::query_as("SELECT key FROM subject_keys WHERE subject = ?")
+                .bind(subject)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(key_store_error)?;
+
+        row.map(|(key,)| restore(subject, key)).transpose()
+    }
+
+    async fn load_or_create_key(&self, subject: &str) -> Result<SubjectKey, CqrsError> {
+        // A single statement, so that a key created concurrently for the same subject wins
+        // over this one: the no-op update makes the existing key returned.
+        let (key,): (Vec<u8>,) = sqlx::query_as(
+            "INSERT INTO subject_keys (subject, key) VALUES (?, ?)
+             ON CONFLICT(subject) DO UPDATE SET key = key
+             RETURNING key",
+        )
+        .bind(subject)
+        .bind(SubjectKey::generate().as_bytes().as_slice())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(key_store_error)?;
+
+        restore(subject, key)
+    }
+
+    async fn delete_key(&self, subject: &str) -> Result<bool, CqrsError> {
+        let result = sqlx::query("DELETE FROM subject_keys WHERE subject = ?")
+            .bind(subject)
+            .execute(&self.pool)
+            .await
+            .map_err(key_store_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
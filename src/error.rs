@@ -20,6 +20,14 @@ pub enum CqrsError {
     #[error("snapshot store error: {0}")]
     SnapshotStore(String),
 
+    /// An event archive error occurred.
+    #[error("event archive error: {0}")]
+    EventArchive(String),
+
+    /// A key store error occurred.
+    #[error("key store error: {0}")]
+    KeyStore(String),
+
     /// A domain/business logic error occurred.
     #[error("{0}")]
     Domain(String),
@@ -36,6 +44,16 @@ pub enum CqrsError {
     #[error("{0}")]
     CommandInvariantSource(#[source] Box<dyn StdError + Send + Sync>),
 
+    /// A stored event could not be applied to an aggregate, e.g. because of corrupt data or an
+    /// event that is impossible in the current state.
+    #[error("failed to apply event {event_id} (version {version}): {source}")]
+    EventApply {
+        event_id: String,
+        version: u64,
+        #[source]
+        source: Box<CqrsError>,
+    },
+
     /// A concurrency conflict occurred (optimistic locking).
     #[error("concurrency conflict: expected version {expected_version}, got {actual_version}")]
     Conflict {
@@ -43,6 +61,19 @@ pub enum CqrsError {
         actual_version: u64,
     },
 
+    /// An aggregate was accessed on behalf of a tenant it doesn't belong to. The tenant owning
+    /// it is not disclosed.
+    #[error("aggregate {aggregate_id} doesn't belong to tenant {tenant_id}")]
+    TenantMismatch {
+        aggregate_id: String,
+        tenant_id: String,
+    },
+
+    /// The events of an aggregate preceding `version` were archived, so its state can't be
+    /// replayed from the event store alone.
+    #[error("the events of aggregate {aggregate_id} preceding version {version} were archived")]
+    TruncatedStream { aggregate_id: String, version: u64 },
+
     /// Any other error, with full `anyhow` context and backtrace support.
     #[error(transparent)]
     Other(#[from] anyhow::Error),
@@ -79,6 +110,98 @@ impl CqrsError {
     {
         Self::CommandInvariantSource(Box::new(error))
     }
+
+    /// Converts an error returned by an event store into [`CqrsError::EventStore`], keeping
+    /// `EventStore`, `Conflict` and `TenantMismatch` errors as they are.
+    pub fn into_event_store(self) -> Self {
+        match self {
+            Self::EventStore(_) | Self::Conflict { .. } | Self::TenantMismatch { .. } => self,
+            other => Self::EventStore(other.to_string()),
+        }
+    }
+
+    /// Converts an error reported by an aggregate invariant check into
+    /// [`CqrsError::CommandInvariant`], keeping command invariant errors as they are.
+    pub fn into_invariant(self) -> Self {
+        match self {
+            Self::CommandInvariant(_) | Self::CommandInvariantSource(_) => self,
+            other => Self::CommandInvariant(other.to_string()),
+        }
+    }
+
+    /// Converts an error returned by a snapshot store into [`CqrsError::SnapshotStore`],
+    /// keeping `SnapshotStore` errors as they are.
+    pub fn into_snapshot_store(self) -> Self {
+        match self {
+            Self::SnapshotStore(_) => self,
+            other => Self::SnapshotStore(other.to_string()),
+        }
+    }
+
+    /// Converts an error returned by an event archive into [`CqrsError::EventArchive`],
+    /// keeping `EventArchive` errors as they are.
+    pub fn into_event_archive(self) -> Self {
+        match self {
+            Self::EventArchive(_) => self,
+            other => Self::EventArchive(other.to_string()),
+        }
+    }
+
+    /// Converts an error returned by a key store into [`CqrsError::KeyStore`], keeping
+    /// `KeyStore` errors as they are.
+    pub fn into_key_store(self) -> Self {
+        match self {
+            Self::KeyStore(_) => self,
+            other => Self::KeyStore(other.to_string()),
+        }
+    }
+
+    // Copies an error reported to several commands at once (e.g. a failed batch append),
+    // flattening the variants holding non-cloneable sources into their message.
+    pub(crate) fn duplicate(&self) -> Self {
+        match self {
+            Self::AggregateNotFound(message) => Self::AggregateNotFound(message.clone()),
+            Self::EventStore(message) => Self::EventStore(message.clone()),
+            Self::SnapshotStore(message) => Self::SnapshotStore(message.clone()),
+            Self::EventArchive(message) => Self::EventArchive(message.clone()),
+            Self::KeyStore(message) => Self::KeyStore(message.clone()),
+            Self::Domain(message) => Self::Domain(message.clone()),
+            Self::CommandInvariant(message) => Self::CommandInvariant(message.clone()),
+            Self::Conflict {
+                expected_version,
+                actual_version,
+            } => Self::Conflict {
+                expected_version: *expected_version,
+                actual_version: *actual_version,
+            },
+            Self::TenantMismatch {
+                aggregate_id,
+                tenant_id,
+            } => Self::TenantMismatch {
+                aggregate_id: aggregate_id.clone(),
+                tenant_id: tenant_id.clone(),
+            },
+            Self::TruncatedStream {
+                aggregate_id,
+                version,
+            } => Self::TruncatedStream {
+                aggregate_id: aggregate_id.clone(),
+                version: *version,
+            },
+            Self::EventApply {
+                event_id,
+                version,
+                source,
+            } => Self::EventApply {
+                event_id: event_id.clone(),
+                version: *version,
+                source: Box::new(source.duplicate()),
+            },
+            Self::DomainSource(source) => Self::Domain(source.to_string()),
+            Self::CommandInvariantSource(source) => Self::CommandInvariant(source.to_string()),
+            Self::Serialization(_) | Self::Other(_) => Self::Other(anyhow::anyhow!("{self}")),
+        }
+    }
 }
 
 impl From<&str> for CqrsError {
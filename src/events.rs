@@ -5,10 +5,10 @@ use std::future::Future;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
-use crate::CqrsError;
+use crate::{Clock, CqrsError, IdGenerator, SystemClock, UnitOfWork, UuidGenerator};
 
 /// Optional metadata associated with an event.
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct EventMetadata {
     pub command_id: Option<String>,
     pub correlation_id: Option<String>,
@@ -19,26 +19,55 @@ pub struct EventMetadata {
     pub extra: HashMap<String, serde_json::Value>,
 }
 
+/// The personal data carried by an event payload, see [`EventPayload::personal_data`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PersonalData {
+    /// The data subject the personal data belongs to, e.g. a customer ID. It is stored in
+    /// clear, so it must be a pseudonym rather than personal data itself.
+    pub subject: String,
+    /// JSON pointers to the fields of the serialized payload holding personal data, e.g.
+    /// `/GuestCheckedIn/guest_name`.
+    pub fields: Vec<String>,
+}
+
 /// A new event to be persisted by the event store.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NewEvent {
+    /// The unique ID of the event, kept by the event store as [`StoredEvent::id`].
+    pub id: String,
     pub event_type: String,
     pub payload: serde_json::Value,
     #[serde(default)]
     pub metadata: EventMetadata,
     pub timestamp: DateTime<Utc>,
+    /// The personal data carried by the payload, encrypted by event stores supporting it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub personal_data: Option<PersonalData>,
 }
 
 impl NewEvent {
+    /// Creates an event timestamped with the system time, with a time-ordered UUID.
     pub fn from_payload<T: EventPayload>(
         payload: T,
         metadata: EventMetadata,
+    ) -> Result<Self, CqrsError> {
+        Self::from_payload_with(payload, metadata, &SystemClock, &UuidGenerator)
+    }
+
+    /// Creates an event timestamped by `clock`, with an ID generated by `id_generator`.
+    pub fn from_payload_with<T: EventPayload>(
+        payload: T,
+        metadata: EventMetadata,
+        clock: &(impl Clock + ?Sized),
+        id_generator: &(impl IdGenerator + ?Sized),
     ) -> Result<Self, CqrsError> {
         Ok(Self {
+            id: id_generator.next_id(),
             event_type: payload.name(),
+            personal_data: payload.personal_data(),
             payload: serde_json::to_value(payload)?,
             metadata,
-            timestamp: Utc::now(),
+            timestamp: clock.now(),
         })
     }
 }
@@ -46,6 +75,7 @@ impl NewEvent {
 /// A persisted event envelope.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StoredEvent {
+    /// The unique ID assigned to the event before it was persisted (see [`NewEvent::id`]).
     pub id: String,
     pub aggregate_id: String,
     pub aggregate_type: String,
@@ -73,6 +103,63 @@ pub trait EventPayload: Serialize + DeserializeOwned + Clone + Display {
     fn name(&self) -> String {
         self.to_string()
     }
+
+    /// Returns the personal data carried by the payload, if any. Defaults to `None`.
+    ///
+    /// Event stores wrapped in an `EncryptedEventStore` (`encryption` feature) encrypt these
+    /// fields with the key of their subject, so that forgetting the subject makes them
+    /// unreadable.
+    fn personal_data(&self) -> Option<PersonalData> {
+        None
+    }
+}
+
+/// The expected state of a stream when appending events to it.
+///
+/// Event stores check it against the actual version of the stream before appending, and
+/// commands declare it through [`Command::expected_version`](crate::Command::expected_version)
+/// to state whether they create a new aggregate or require an existing one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExpectedVersion {
+    /// No check is performed: the events are appended whatever the stream state is.
+    #[default]
+    Any,
+    /// The stream must not exist yet (version `0`).
+    NoStream,
+    /// The stream must already exist (version greater than `0`).
+    StreamExists,
+    /// The stream must be exactly at the given version.
+    Exact(u64),
+}
+
+impl ExpectedVersion {
+    /// Checks the expectation against the actual version of the stream.
+    ///
+    /// Returns [`CqrsError::AggregateNotFound`] when an existing stream was expected but
+    /// none was found, and [`CqrsError::Conflict`] for any other version mismatch.
+    pub fn check(&self, aggregate_id: &str, actual_version: u64) -> Result<(), CqrsError> {
+        match *self {
+            Self::Any => Ok(()),
+            Self::NoStream if actual_version == 0 => Ok(()),
+            Self::NoStream => Err(CqrsError::Conflict {
+                expected_version: 0,
+                actual_version,
+            }),
+            Self::StreamExists if actual_version > 0 => Ok(()),
+            Self::StreamExists => Err(CqrsError::AggregateNotFound(aggregate_id.to_string())),
+            Self::Exact(expected_version) if expected_version == actual_version => Ok(()),
+            Self::Exact(expected_version) => Err(CqrsError::Conflict {
+                expected_version,
+                actual_version,
+            }),
+        }
+    }
+}
+
+impl From<u64> for ExpectedVersion {
+    fn from(version: u64) -> Self {
+        Self::Exact(version)
+    }
 }
 
 /// The `EventStore` trait defines the behavior for storing and loading events,
@@ -82,21 +169,169 @@ pub trait EventPayload: Serialize + DeserializeOwned + Clone + Display {
 /// use interior mutability (e.g., `RwLock`, `Mutex`) or connection pools.
 ///
 /// The `expected_version` parameter on `save_events` enables optimistic concurrency control.
-/// Pass `0` for a new aggregate. If the actual version doesn't match, return `CqrsError::Conflict`.
+/// Implementations should validate it with [`ExpectedVersion::check`] against the actual version
+/// of the stream, so that mismatches are reported as `CqrsError::Conflict` or
+/// `CqrsError::AggregateNotFound` consistently across stores.
 pub trait EventStore: Send + Sync {
-    /// Saves events to the event store. `expected_version` is the last known version of the aggregate.
+    /// Saves events to the event store. `expected_version` is the expected state of the stream.
     fn save_events(
         &self,
         aggregate_type: &str,
         aggregate_id: &str,
         events: &[NewEvent],
-        expected_version: u64,
+        expected_version: ExpectedVersion,
     ) -> impl Future<Output = Result<Vec<StoredEvent>, CqrsError>> + Send;
 
+    /// Saves the appends of a [`UnitOfWork`] atomically: either all of them are persisted, or
+    /// none is. Returns the persisted events of each append, in the same order.
+    ///
+    /// The default implementation only supports units of work touching a single stream, which
+    /// are delegated to [`EventStore::save_events`]; stores able to commit several streams in
+    /// a single transaction should override it.
+    fn save_unit_of_work(
+        &self,
+        unit: &UnitOfWork,
+    ) -> impl Future<Output = Result<Vec<Vec<StoredEvent>>, CqrsError>> + Send {
+        async move {
+            match unit.appends() {
+                [] => Ok(vec![]),
+                [append] => Ok(vec![
+                    self.save_events(
+                        &append.aggregate_type,
+                        &append.aggregate_id,
+                        &append.events,
+                        append.expected_version,
+                    )
+                    .await?,
+                ]),
+                _ => Err(CqrsError::EventStore(
+                    "atomic commits across several streams are not supported by this event store"
+                        .to_string(),
+                )),
+            }
+        }
+    }
+
     /// Loads events from the event store. Returns the events and the current version.
+    ///
+    /// A stream that doesn't exist is not an error: implementations must return an empty list
+    /// and version `0`. Errors are reserved for actual store failures (connection issues,
+    /// corrupt rows, etc.) and are propagated to the caller.
     fn load_events(
         &self,
         aggregate_type: &str,
         aggregate_id: &str,
     ) -> impl Future<Output = Result<(Vec<StoredEvent>, u64), CqrsError>> + Send;
+
+    /// Returns the current version of a stream, `0` if it doesn't exist.
+    ///
+    /// The default implementation loads the whole stream; stores should override it with a
+    /// cheaper query.
+    fn stream_version(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+    ) -> impl Future<Output = Result<u64, CqrsError>> + Send {
+        async move {
+            let (_, version) = self.load_events(aggregate_type, aggregate_id).await?;
+            Ok(version)
+        }
+    }
+
+    /// Returns the IDs of the streams of an aggregate type holding at least one event, in the
+    /// order the streams were created.
+    ///
+    /// Used by tools processing every aggregate of a type (e.g. [`ReplayVerifier`]). The
+    /// default implementation returns an error, as not every store can enumerate its streams.
+    ///
+    /// [`ReplayVerifier`]: crate::ReplayVerifier
+    fn stream_ids(
+        &self,
+        _aggregate_type: &str,
+    ) -> impl Future<Output = Result<Vec<String>, CqrsError>> + Send {
+        async {
+            Err(CqrsError::EventStore(
+                "listing streams is not supported by this event store".to_string(),
+            ))
+        }
+    }
+
+    /// Deletes the events of a stream preceding `version`, keeping the event at `version` and
+    /// the following ones so that the stream keeps its current version. Returns the number of
+    /// deleted events.
+    ///
+    /// Used to truncate streams once their old events were archived (see [`StreamArchiver`]).
+    /// The default implementation returns an error, as not every store can delete events.
+    ///
+    /// [`StreamArchiver`]: crate::StreamArchiver
+    fn delete_events_before(
+        &self,
+        _aggregate_type: &str,
+        _aggregate_id: &str,
+        _version: u64,
+    ) -> impl Future<Output = Result<usize, CqrsError>> + Send {
+        async {
+            Err(CqrsError::EventStore(
+                "deleting events is not supported by this event store".to_string(),
+            ))
+        }
+    }
+
+    /// Loads the events of a stream up to and including `version`. Returns the events and the
+    /// version of the last one.
+    ///
+    /// The default implementation filters the result of [`EventStore::load_events`]; stores
+    /// should override it to push the bound down to the storage engine.
+    fn load_events_to_version(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+        version: u64,
+    ) -> impl Future<Output = Result<(Vec<StoredEvent>, u64), CqrsError>> + Send {
+        async move {
+            let (mut events, _) = self.load_events(aggregate_type, aggregate_id).await?;
+            events.retain(|e| e.version <= version);
+            let version = events.last().map(|e| e.version).unwrap_or(0);
+            Ok((events, version))
+        }
+    }
+
+    /// Loads the events of a stream following `version`. Returns the events and the current
+    /// version of the stream.
+    ///
+    /// Used to catch up from a snapshot. The default implementation filters the result of
+    /// [`EventStore::load_events`]; stores should override it to push the bound down to the
+    /// storage engine.
+    fn load_events_after(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+        version: u64,
+    ) -> impl Future<Output = Result<(Vec<StoredEvent>, u64), CqrsError>> + Send {
+        async move {
+            let (mut events, current_version) =
+                self.load_events(aggregate_type, aggregate_id).await?;
+            events.retain(|e| e.version > version);
+            Ok((events, current_version))
+        }
+    }
+
+    /// Loads the events of a stream recorded at or before `timestamp`. Returns the events and
+    /// the version of the last one.
+    ///
+    /// The default implementation filters the result of [`EventStore::load_events`]; stores
+    /// should override it to push the bound down to the storage engine.
+    fn load_events_as_of(
+        &self,
+        aggregate_type: &str,
+        aggregate_id: &str,
+        timestamp: DateTime<Utc>,
+    ) -> impl Future<Output = Result<(Vec<StoredEvent>, u64), CqrsError>> + Send {
+        async move {
+            let (mut events, _) = self.load_events(aggregate_type, aggregate_id).await?;
+            events.retain(|e| e.timestamp <= timestamp);
+            let version = events.last().map(|e| e.version).unwrap_or(0);
+            Ok((events, version))
+        }
+    }
 }
@@ -0,0 +1,51 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// The `IdGenerator` trait generates the IDs of new events, so that they can be controlled in
+/// tests.
+pub trait IdGenerator: Send + Sync {
+    fn next_id(&self) -> String;
+}
+
+impl<G: IdGenerator + ?Sized> IdGenerator for Arc<G> {
+    fn next_id(&self) -> String {
+        (**self).next_id()
+    }
+}
+
+/// An [`IdGenerator`] returning time-ordered (v7) UUIDs. This is the default.
+///
+/// IDs are unique across aggregate types and stores, and sort in creation order (strictly
+/// within a process), which keeps them index-friendly and usable to deduplicate events
+/// downstream.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UuidGenerator;
+
+impl IdGenerator for UuidGenerator {
+    fn next_id(&self) -> String {
+        uuid::Uuid::now_v7().to_string()
+    }
+}
+
+/// An [`IdGenerator`] returning `{prefix}-1`, `{prefix}-2`, etc.
+#[derive(Debug)]
+pub struct SequentialIdGenerator {
+    prefix: String,
+    last: AtomicU64,
+}
+
+impl SequentialIdGenerator {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            last: AtomicU64::new(0),
+        }
+    }
+}
+
+impl IdGenerator for SequentialIdGenerator {
+    fn next_id(&self) -> String {
+        let id = self.last.fetch_add(1, Ordering::Relaxed) + 1;
+        format!("{}-{id}", self.prefix)
+    }
+}
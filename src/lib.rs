@@ -7,39 +7,102 @@
 //!
 //! - Provides traits for defining aggregates, commands, and event consumers.
 //! - Manages aggregates' state and events handling with optimistic concurrency.
-//! - Supports event stores and snapshot stores.
+//! - Supports event stores and snapshot stores, with in-memory and SQLite (`sqlite` feature)
+//!   snapshot stores included.
+//! - Supports reliable event publishing through a transactional outbox.
+//! - Supports archiving the events preceding confirmed snapshots to a cold store.
+//! - Supports erasing personal data from events by deleting the keys encrypting it
+//!   (`encryption` feature).
 //! - Supports queries on read models.
+//! - Supports multi-tenancy, with event stores and CQRS instances scoped to a tenant.
 //! - All trait methods take `&self` for easy concurrent usage.
 //! - No `async_trait` dependency — uses native async fn in traits.
+//! - Optional derive macros for events and aggregates (`derive` feature).
+//! - Conformance suites for custom event and snapshot stores (`testkit` feature).
+//! - Replay verification, detecting aggregates that are not rebuilt deterministically.
 //!
 //! For more detailed documentation, refer to the specific modules and types provided by MiniCQRS/ES.
 
 pub use ::anyhow;
 
+#[cfg(feature = "derive")]
+pub use mini_cqrs_es_derive::{Aggregate, EventPayload};
+
 mod error;
 pub use error::CqrsError;
 
+#[cfg(feature = "encryption")]
+mod encryption;
+#[cfg(all(feature = "encryption", feature = "sqlite"))]
+pub use encryption::SqliteKeyStore;
+#[cfg(feature = "encryption")]
+pub use encryption::{EncryptedEventStore, InMemoryKeyStore, KeyStore, SubjectKey};
+
 mod events;
-pub use events::{EventMetadata, EventPayload, EventStore, NewEvent, StoredEvent};
+pub use events::{
+    EventMetadata, EventPayload, EventStore, ExpectedVersion, NewEvent, PersonalData,
+    StoredEvent,
+};
 
 mod aggregate;
 pub use aggregate::{
-    manager::{AggregateManager, SimpleAggregateManager, SnapshotAggregateManager},
+    cache::CachedAggregateManager,
+    manager::{AggregateManager, NoEventStore, SimpleAggregateManager, SnapshotAggregateManager},
     snapshot::{AggregateSnapshot, SnapshotStore},
     Aggregate,
 };
 
+mod archive;
+#[cfg(feature = "sqlite")]
+pub use archive::SqliteEventArchive;
+pub use archive::{EventArchive, JsonlFileArchive, StreamArchiver};
+
+mod clock;
+pub use clock::{Clock, FixedClock, SteppedClock, SystemClock};
+
 mod command;
-pub use command::Command;
+pub use command::{Command, CommandReply, CommandWithReply};
 
 mod consumer;
 pub use consumer::{EventConsumer, EventConsumers};
 
 mod cqrs;
-pub use cqrs::{Cqrs, SimpleCqrs};
+pub use cqrs::{BatchOutcome, Cqrs, SimpleCqrs, Simulation};
+
+mod id;
+pub use id::{IdGenerator, SequentialIdGenerator, UuidGenerator};
+
+mod middleware;
+pub use middleware::{CommandContext, CommandMiddleware, CommandMiddlewares};
+
+mod outbox;
+pub use outbox::{InMemoryPublisher, OutboxEntry, OutboxRelay, OutboxStore, Publisher};
 
 mod query;
 pub use query::{Query, QueryRunner};
 
+mod registry;
+pub use registry::{CommandRegistry, DispatchResult};
+
 mod repository;
 pub use repository::Repository;
+
+mod serialized;
+pub use serialized::SerializedCqrs;
+
+mod tenant;
+pub use tenant::TenantEventStore;
+
+#[cfg(feature = "testkit")]
+pub mod testkit;
+
+mod snapshot_stores;
+#[cfg(feature = "sqlite")]
+pub use snapshot_stores::SqliteSnapshotStore;
+pub use snapshot_stores::InMemorySnapshotStore;
+
+mod unit_of_work;
+pub use unit_of_work::{StreamAppend, UnitOfWork};
+
+mod verify;
+pub use verify::{Divergence, DivergenceKind, NoSnapshotStore, ReplayReport, ReplayVerifier};
@@ -0,0 +1,174 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Instant;
+
+use crate::{CqrsError, EventMetadata, NewEvent, StoredEvent};
+
+/// Information about the command being executed, shared with every [`CommandMiddleware`].
+#[derive(Clone, Debug)]
+pub struct CommandContext {
+    /// The type name of the command.
+    pub command_name: &'static str,
+    /// The type name of the aggregate targeted by the command.
+    pub aggregate_type: &'static str,
+    /// The ID of the aggregate targeted by the command.
+    pub aggregate_id: String,
+    /// The metadata attached to the events emitted by the command. Middlewares can enrich it
+    /// in [`CommandMiddleware::before_handle`].
+    pub metadata: EventMetadata,
+    /// When the execution of the command started.
+    pub started_at: Instant,
+    /// Whether the command is only previewed with [`Cqrs::simulate`], its events never being
+    /// saved.
+    ///
+    /// [`Cqrs::simulate`]: crate::Cqrs::simulate
+    pub dry_run: bool,
+}
+
+/// The `CommandMiddleware` trait defines cross-cutting behavior around command execution
+/// (authorization, validation, logging, timing, etc.).
+///
+/// Every hook has a no-op default implementation, and returning an error from any of them
+/// aborts the execution with that error:
+/// - `before_handle` runs after the aggregate is loaded, before the command is handled;
+/// - `after_handle` runs after the command is handled, before its events are saved;
+/// - `after_commit` runs once the events are saved and processed by the consumers.
+///
+/// Commands previewed with [`Cqrs::simulate`] run `before_handle` and `after_handle` with
+/// [`CommandContext::dry_run`] set, so that rules enforced by middlewares still apply to them;
+/// middlewares with side effects (logging, auditing, metrics, rate limiting) should skip them.
+/// `after_commit` never runs for them.
+///
+/// Methods take `&self` to allow concurrent access.
+///
+/// [`Cqrs::simulate`]: crate::Cqrs::simulate
+pub trait CommandMiddleware: Send + Sync {
+    fn before_handle(
+        &self,
+        _context: &mut CommandContext,
+    ) -> impl Future<Output = Result<(), CqrsError>> + Send {
+        async { Ok(()) }
+    }
+
+    fn after_handle(
+        &self,
+        _context: &CommandContext,
+        _events: &[NewEvent],
+    ) -> impl Future<Output = Result<(), CqrsError>> + Send {
+        async { Ok(()) }
+    }
+
+    fn after_commit(
+        &self,
+        _context: &CommandContext,
+        _events: &[StoredEvent],
+    ) -> impl Future<Output = Result<(), CqrsError>> + Send {
+        async { Ok(()) }
+    }
+}
+
+type BoxFuture<'a> = Pin<Box<dyn Future<Output = Result<(), CqrsError>> + Send + 'a>>;
+
+// Internal dyn-compatible wrapper so we can store middlewares in a Vec<Box<dyn ...>>.
+trait DynCommandMiddleware: Send + Sync {
+    fn before_handle_dyn<'a>(&'a self, context: &'a mut CommandContext) -> BoxFuture<'a>;
+
+    fn after_handle_dyn<'a>(
+        &'a self,
+        context: &'a CommandContext,
+        events: &'a [NewEvent],
+    ) -> BoxFuture<'a>;
+
+    fn after_commit_dyn<'a>(
+        &'a self,
+        context: &'a CommandContext,
+        events: &'a [StoredEvent],
+    ) -> BoxFuture<'a>;
+}
+
+impl<T: CommandMiddleware> DynCommandMiddleware for T {
+    fn before_handle_dyn<'a>(&'a self, context: &'a mut CommandContext) -> BoxFuture<'a> {
+        Box::pin(CommandMiddleware::before_handle(self, context))
+    }
+
+    fn after_handle_dyn<'a>(
+        &'a self,
+        context: &'a CommandContext,
+        events: &'a [NewEvent],
+    ) -> BoxFuture<'a> {
+        Box::pin(CommandMiddleware::after_handle(self, context, events))
+    }
+
+    fn after_commit_dyn<'a>(
+        &'a self,
+        context: &'a CommandContext,
+        events: &'a [StoredEvent],
+    ) -> BoxFuture<'a> {
+        Box::pin(CommandMiddleware::after_commit(self, context, events))
+    }
+}
+
+/// A stack of command middlewares, run in the order they were added.
+///
+/// Use the builder pattern to add middlewares:
+///
+/// ```rust,ignore
+/// let middlewares = CommandMiddlewares::new()
+///     .with(AuthorizationMiddleware::new(policy))
+///     .with(TimingMiddleware {});
+/// ```
+pub struct CommandMiddlewares {
+    middlewares: Vec<Box<dyn DynCommandMiddleware>>,
+}
+
+impl CommandMiddlewares {
+    pub fn new() -> Self {
+        Self {
+            middlewares: Vec::new(),
+        }
+    }
+
+    /// Adds a middleware to the stack.
+    pub fn with(mut self, middleware: impl CommandMiddleware + 'static) -> Self {
+        self.middlewares.push(Box::new(middleware));
+        self
+    }
+
+    /// Runs the `before_handle` hook of every middleware, stopping at the first error.
+    pub async fn before_handle(&self, context: &mut CommandContext) -> Result<(), CqrsError> {
+        for middleware in &self.middlewares {
+            middleware.before_handle_dyn(context).await?;
+        }
+        Ok(())
+    }
+
+    /// Runs the `after_handle` hook of every middleware, stopping at the first error.
+    pub async fn after_handle(
+        &self,
+        context: &CommandContext,
+        events: &[NewEvent],
+    ) -> Result<(), CqrsError> {
+        for middleware in &self.middlewares {
+            middleware.after_handle_dyn(context, events).await?;
+        }
+        Ok(())
+    }
+
+    /// Runs the `after_commit` hook of every middleware, stopping at the first error.
+    pub async fn after_commit(
+        &self,
+        context: &CommandContext,
+        events: &[StoredEvent],
+    ) -> Result<(), CqrsError> {
+        for middleware in &self.middlewares {
+            middleware.after_commit_dyn(context, events).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for CommandMiddlewares {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,144 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use crate::{CqrsError, StoredEvent};
+
+/// An event recorded in the outbox, waiting to be published.
+#[derive(Clone, Debug)]
+pub struct OutboxEntry {
+    /// The position of the entry in the outbox.
+    pub id: i64,
+    /// The persisted event to publish.
+    pub event: StoredEvent,
+}
+
+/// The `OutboxStore` trait gives access to the events recorded in an outbox.
+///
+/// Event stores implementing it are expected to record each appended event in the outbox
+/// within the same transaction as the stream append, so that no event can be committed
+/// without eventually being published.
+pub trait OutboxStore: Send + Sync {
+    /// Returns up to `limit` undelivered entries, oldest first.
+    fn pending(
+        &self,
+        limit: usize,
+    ) -> impl Future<Output = Result<Vec<OutboxEntry>, CqrsError>> + Send;
+
+    /// Marks the given entries as delivered, so they won't be returned by `pending` anymore.
+    fn mark_delivered(&self, ids: &[i64]) -> impl Future<Output = Result<(), CqrsError>> + Send;
+}
+
+/// The `Publisher` trait defines how outbox events are delivered to the outside world
+/// (message brokers, webhooks, other services, etc.).
+///
+/// Delivery is at-least-once: an event can be published again if the relay stops between
+/// publishing it and marking it as delivered, so publishers and their subscribers should be
+/// idempotent.
+pub trait Publisher: Send + Sync {
+    fn publish(&self, event: &StoredEvent) -> impl Future<Output = Result<(), CqrsError>> + Send;
+}
+
+/// Relays events from an [`OutboxStore`] to a [`Publisher`], marking them as delivered.
+///
+/// ```rust,ignore
+/// let relay = OutboxRelay::new(event_store.clone(), KafkaPublisher::new(producer));
+///
+/// // Runs forever, sleeping when the outbox is empty.
+/// relay.run(|| tokio::time::sleep(Duration::from_secs(1))).await?;
+/// ```
+pub struct OutboxRelay<OS, P>
+where
+    OS: OutboxStore,
+    P: Publisher,
+{
+    outbox: OS,
+    publisher: P,
+    batch_size: usize,
+}
+
+impl<OS, P> OutboxRelay<OS, P>
+where
+    OS: OutboxStore,
+    P: Publisher,
+{
+    /// Creates a new relay publishing up to 100 events per batch.
+    pub fn new(outbox: OS, publisher: P) -> Self {
+        Self {
+            outbox,
+            publisher,
+            batch_size: 100,
+        }
+    }
+
+    /// Sets the maximum number of events published per batch.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Publishes one batch of pending events in order, and returns how many were delivered.
+    ///
+    /// Publishing stops at the first failure: the events delivered so far are marked as such
+    /// and the error is returned, leaving the remaining ones pending for the next attempt.
+    pub async fn relay_pending(&self) -> Result<usize, CqrsError> {
+        let entries = self.outbox.pending(self.batch_size).await?;
+        let mut delivered = Vec::with_capacity(entries.len());
+
+        for entry in &entries {
+            if let Err(error) = self.publisher.publish(&entry.event).await {
+                if !delivered.is_empty() {
+                    self.outbox.mark_delivered(&delivered).await?;
+                }
+                return Err(error);
+            }
+            delivered.push(entry.id);
+        }
+
+        if !delivered.is_empty() {
+            self.outbox.mark_delivered(&delivered).await?;
+        }
+        Ok(delivered.len())
+    }
+
+    /// Relays events until an error occurs, awaiting `idle` whenever the outbox is empty.
+    ///
+    /// `idle` is typically a sleep from the async runtime in use, which keeps the relay
+    /// runtime-agnostic.
+    pub async fn run<F, Fut>(&self, idle: F) -> Result<(), CqrsError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        loop {
+            if self.relay_pending().await? == 0 {
+                idle().await;
+            }
+        }
+    }
+}
+
+/// A [`Publisher`] collecting events in memory, useful for tests.
+///
+/// Clones share the same list of published events.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryPublisher {
+    published: Arc<Mutex<Vec<StoredEvent>>>,
+}
+
+impl InMemoryPublisher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the events published so far, in order.
+    pub fn published(&self) -> Vec<StoredEvent> {
+        self.published.lock().unwrap().clone()
+    }
+}
+
+impl Publisher for InMemoryPublisher {
+    async fn publish(&self, event: &StoredEvent) -> Result<(), CqrsError> {
+        self.published.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
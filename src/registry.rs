@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+
+use serde::de::DeserializeOwned;
+
+use crate::{Aggregate, Command, Cqrs, CqrsError};
+
+/// The type-erased outcome of a command dispatched through a [`CommandRegistry`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DispatchResult {
+    /// The name the command was registered with.
+    pub command_name: String,
+    /// The type name of the aggregate targeted by the command.
+    pub aggregate_type: &'static str,
+    /// The ID of the aggregate targeted by the command.
+    pub aggregate_id: String,
+}
+
+type DispatchFuture<'a> = Pin<Box<dyn Future<Output = Result<String, CqrsError>> + Send + 'a>>;
+
+type DispatchFn<Q> = for<'a> fn(&'a Q, &'a str, serde_json::Value) -> DispatchFuture<'a>;
+
+struct Route<Q> {
+    aggregate_type: &'static str,
+    dispatch: DispatchFn<Q>,
+}
+
+/// A registry mapping command names to command types, so that serialized commands (e.g.
+/// received by an HTTP or message-queue gateway) can be executed through a [`Cqrs`]
+/// without matching on every command by hand.
+///
+/// Commands are JSON objects carrying their registered name in a `type` field; the remaining
+/// fields are deserialized into the command:
+///
+/// ```rust,ignore
+/// let registry = CommandRegistry::new()
+///     .register::<CmdCheckIn>("CheckIn")
+///     .register::<CmdCheckOut>("CheckOut");
+///
+/// let command = json!({ "type": "CheckIn", "room_number": 1, "guest_name": "Alice" });
+/// let result = registry.dispatch(&cqrs, "42", command).await?;
+/// ```
+pub struct CommandRegistry<Q>
+where
+    Q: Cqrs,
+{
+    routes: HashMap<String, Route<Q>>,
+}
+
+impl<Q> CommandRegistry<Q>
+where
+    Q: Cqrs,
+{
+    pub fn new() -> Self {
+        Self {
+            routes: HashMap::new(),
+        }
+    }
+
+    /// Registers the command `C` under `name`. A command registered twice under the same
+    /// name replaces the previous one.
+    pub fn register<C>(mut self, name: impl Into<String>) -> Self
+    where
+        C: Command + DeserializeOwned + 'static,
+    {
+        self.routes.insert(
+            name.into(),
+            Route {
+                aggregate_type: std::any::type_name::<C::Aggregate>(),
+                dispatch: dispatch_command::<Q, C>,
+            },
+        );
+        self
+    }
+
+    /// Returns `true` if a command is registered under `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.routes.contains_key(name)
+    }
+
+    /// Returns the type name of the aggregate targeted by the command registered under `name`.
+    pub fn aggregate_type(&self, name: &str) -> Option<&'static str> {
+        self.routes.get(name).map(|route| route.aggregate_type)
+    }
+
+    /// Dispatches a command whose name is carried by its `type` field.
+    ///
+    /// Returns [`CqrsError::CommandInvariant`] if the `type` field is missing or no command is
+    /// registered under it.
+    pub async fn dispatch(
+        &self,
+        cqrs: &Q,
+        aggregate_id: &str,
+        mut command: serde_json::Value,
+    ) -> Result<DispatchResult, CqrsError> {
+        let name = match command
+            .as_object_mut()
+            .and_then(|fields| fields.remove("type"))
+        {
+            Some(serde_json::Value::String(name)) => name,
+            _ => {
+                return Err(CqrsError::invariant(
+                    "command must be an object with a string `type` field",
+                ));
+            }
+        };
+
+        self.dispatch_named(cqrs, &name, aggregate_id, command)
+            .await
+    }
+
+    /// Dispatches the command registered under `name`, deserializing it from `command`.
+    ///
+    /// Returns [`CqrsError::CommandInvariant`] if no command is registered under `name` or
+    /// the aggregate ID can't be parsed, and [`CqrsError::Serialization`] if the command
+    /// can't be deserialized.
+    pub async fn dispatch_named(
+        &self,
+        cqrs: &Q,
+        name: &str,
+        aggregate_id: &str,
+        command: serde_json::Value,
+    ) -> Result<DispatchResult, CqrsError> {
+        let route = self
+            .routes
+            .get(name)
+            .ok_or_else(|| CqrsError::invariant(format!("unknown command `{name}`")))?;
+
+        let aggregate_id = (route.dispatch)(cqrs, aggregate_id, command).await?;
+
+        Ok(DispatchResult {
+            command_name: name.to_string(),
+            aggregate_type: route.aggregate_type,
+            aggregate_id,
+        })
+    }
+}
+
+impl<Q> Default for CommandRegistry<Q>
+where
+    Q: Cqrs,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn dispatch_command<'a, Q, C>(
+    cqrs: &'a Q,
+    aggregate_id: &'a str,
+    command: serde_json::Value,
+) -> DispatchFuture<'a>
+where
+    Q: Cqrs,
+    C: Command + DeserializeOwned + 'static,
+{
+    Box::pin(async move {
+        let id = <C::Aggregate as Aggregate>::Id::from_str(aggregate_id).map_err(|_| {
+            CqrsError::invariant(format!(
+                "invalid aggregate id `{aggregate_id}` for {}",
+                std::any::type_name::<C::Aggregate>()
+            ))
+        })?;
+        let command: C = serde_json::from_value(command)?;
+
+        let id = cqrs.execute(&id, &command).await?;
+        Ok(id.to_string())
+    })
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_lock::{Mutex as AsyncMutex, MutexGuardArc};
+
+use crate::{
+    query::QueryRunner, Aggregate, BatchOutcome, Command, CommandReply, CommandWithReply, Cqrs,
+    CqrsError, Simulation,
+};
+
+type LockKey = (&'static str, String);
+
+/// A map of per-aggregate locks. Locks are created on demand and evicted as soon as no
+/// command holds or waits for them, so idle aggregates don't use any memory.
+#[derive(Default)]
+struct AggregateLocks {
+    locks: Mutex<HashMap<LockKey, Arc<AsyncMutex<()>>>>,
+}
+
+impl AggregateLocks {
+    async fn acquire(&self, key: LockKey) -> AggregateLock<'_> {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        // Built before waiting, so that the entry is evicted even if the wait is cancelled.
+        let mut aggregate_lock = AggregateLock {
+            guard: None,
+            key,
+            locks: self,
+        };
+        aggregate_lock.guard = Some(lock.lock_arc().await);
+        aggregate_lock
+    }
+}
+
+struct AggregateLock<'a> {
+    guard: Option<MutexGuardArc<()>>,
+    key: LockKey,
+    locks: &'a AggregateLocks,
+}
+
+impl Drop for AggregateLock<'_> {
+    fn drop(&mut self) {
+        self.guard.take();
+
+        // Only the map holds the lock anymore: nobody is waiting for it.
+        let mut locks = self.locks.locks.lock().unwrap();
+        if locks
+            .get(&self.key)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.key);
+        }
+    }
+}
+
+/// A [`Cqrs`] decorator executing commands one at a time per aggregate.
+///
+/// Commands targeting the same aggregate wait for each other instead of loading the same
+/// version concurrently and failing with [`CqrsError::Conflict`], while commands targeting
+/// different aggregates still run concurrently. Serialization only applies within the
+/// process: concurrent writers in other processes are still detected by the optimistic
+/// concurrency checks of the event store.
+///
+/// ```rust,ignore
+/// let cqrs = Arc::new(SerializedCqrs::new(SimpleCqrs::new(manager, store, consumers)));
+/// ```
+pub struct SerializedCqrs<Q>
+where
+    Q: Cqrs,
+{
+    inner: Q,
+    locks: AggregateLocks,
+}
+
+impl<Q> SerializedCqrs<Q>
+where
+    Q: Cqrs,
+{
+    pub fn new(inner: Q) -> Self {
+        Self {
+            inner,
+            locks: AggregateLocks::default(),
+        }
+    }
+
+    /// Returns the wrapped [`Cqrs`].
+    pub fn inner(&self) -> &Q {
+        &self.inner
+    }
+
+    /// Returns the number of aggregates currently locked or waited for.
+    pub fn active_locks(&self) -> usize {
+        self.locks.locks.lock().unwrap().len()
+    }
+}
+
+fn lock_key<A: Aggregate>(aggregate_id: &A::Id) -> LockKey {
+    (std::any::type_name::<A>(), aggregate_id.to_string())
+}
+
+impl<Q> Cqrs for SerializedCqrs<Q>
+where
+    Q: Cqrs,
+{
+    async fn execute<C>(
+        &self,
+        aggregate_id: &<C::Aggregate as Aggregate>::Id,
+        command: &C,
+    ) -> Result<<C::Aggregate as Aggregate>::Id, CqrsError>
+    where
+        C: Command,
+    {
+        let _lock = self
+            .locks
+            .acquire(lock_key::<C::Aggregate>(aggregate_id))
+            .await;
+        self.inner.execute(aggregate_id, command).await
+    }
+
+    async fn execute_with_reply<C>(
+        &self,
+        aggregate_id: &<C::Aggregate as Aggregate>::Id,
+        command: &C,
+    ) -> Result<CommandReply<C::Aggregate, C::Reply>, CqrsError>
+    where
+        C: CommandWithReply,
+    {
+        let _lock = self
+            .locks
+            .acquire(lock_key::<C::Aggregate>(aggregate_id))
+            .await;
+        self.inner.execute_with_reply(aggregate_id, command).await
+    }
+
+    async fn execute_batch<C>(
+        &self,
+        commands: &[(<C::Aggregate as Aggregate>::Id, C)],
+    ) -> BatchOutcome<C::Aggregate>
+    where
+        C: Command,
+    {
+        // Locks are always taken in the same order, so concurrent batches can't deadlock.
+        let mut keys: Vec<LockKey> = commands
+            .iter()
+            .map(|(aggregate_id, _)| lock_key::<C::Aggregate>(aggregate_id))
+            .collect();
+        keys.sort();
+        keys.dedup();
+
+        let mut guards = Vec::with_capacity(keys.len());
+        for key in keys {
+            guards.push(self.locks.acquire(key).await);
+        }
+        self.inner.execute_batch(commands).await
+    }
+
+    async fn simulate<C>(
+        &self,
+        aggregate_id: &<C::Aggregate as Aggregate>::Id,
+        command: &C,
+    ) -> Result<Simulation<C::Aggregate>, CqrsError>
+    where
+        C: Command,
+    {
+        // Simulations don't write anything, so they don't need to wait for other commands.
+        self.inner.simulate(aggregate_id, command).await
+    }
+}
+
+/// Implements `QueryRunner` so you can call `cqrs.query(&q).await`.
+impl<Q> QueryRunner for SerializedCqrs<Q> where Q: Cqrs {}
@@ -0,0 +1,134 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use super::DEFAULT_RETENTION;
+use crate::{Aggregate, AggregateSnapshot, CqrsError, SnapshotStore};
+
+type SnapshotKey = (&'static str, String);
+
+// The snapshots of an aggregate, by version: the payload and the schema version.
+type History = BTreeMap<u64, (serde_json::Value, u32)>;
+
+/// A [`SnapshotStore`] keeping snapshots in memory, for tests and prototypes.
+///
+/// Snapshots of any number of aggregate types are kept serialized, keyed by aggregate type
+/// and ID. The most recent snapshots of each aggregate (3 by default) are kept; older ones
+/// are pruned when a new one is saved. Clones share the same snapshots.
+///
+/// ```rust,ignore
+/// let snapshot_store = InMemorySnapshotStore::new().with_retention(5);
+/// let aggregate_manager =
+///     SnapshotAggregateManager::new(snapshot_store).with_event_store(event_store.clone());
+/// ```
+#[derive(Clone)]
+pub struct InMemorySnapshotStore {
+    snapshots: Arc<Mutex<HashMap<SnapshotKey, History>>>,
+    retention: usize,
+}
+
+impl InMemorySnapshotStore {
+    pub fn new() -> Self {
+        Self {
+            snapshots: Arc::new(Mutex::new(HashMap::new())),
+            retention: DEFAULT_RETENTION,
+        }
+    }
+
+    /// Sets how many snapshots are kept per aggregate. At least one is always kept.
+    pub fn with_retention(mut self, retention: usize) -> Self {
+        self.retention = retention.max(1);
+        self
+    }
+
+    /// Returns the snapshots kept for an aggregate, latest first.
+    pub fn history<T: Aggregate>(&self, aggregate_id: &T::Id) -> Vec<AggregateSnapshot<T>> {
+        let snapshots = self.snapshots.lock().unwrap();
+
+        snapshots
+            .get(&snapshot_key::<T>(aggregate_id))
+            .map(|history| {
+                history
+                    .iter()
+                    .rev()
+                    .map(|entry| restore(aggregate_id, entry))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Removes all but the `keep` latest snapshots of an aggregate. Returns the number of
+    /// removed snapshots.
+    pub fn prune<T: Aggregate>(&self, aggregate_id: &T::Id, keep: usize) -> usize {
+        let mut snapshots = self.snapshots.lock().unwrap();
+
+        match snapshots.get_mut(&snapshot_key::<T>(aggregate_id)) {
+            Some(history) => prune(history, keep),
+            None => 0,
+        }
+    }
+}
+
+impl Default for InMemorySnapshotStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn snapshot_key<T: Aggregate>(aggregate_id: &T::Id) -> SnapshotKey {
+    (std::any::type_name::<T>(), aggregate_id.to_string())
+}
+
+fn restore<T: Aggregate>(
+    aggregate_id: &T::Id,
+    (version, (payload, schema_version)): (&u64, &(serde_json::Value, u32)),
+) -> AggregateSnapshot<T> {
+    AggregateSnapshot::from_stored(
+        aggregate_id.clone(),
+        payload.clone(),
+        *version,
+        *schema_version,
+    )
+}
+
+fn prune(history: &mut History, keep: usize) -> usize {
+    let mut removed = 0;
+    while history.len() > keep {
+        history.pop_first();
+        removed += 1;
+    }
+    removed
+}
+
+impl SnapshotStore for InMemorySnapshotStore {
+    async fn save_snapshot<T>(&self, snapshot: AggregateSnapshot<T>) -> Result<(), CqrsError>
+    where
+        T: Aggregate,
+    {
+        let mut snapshots = self.snapshots.lock().unwrap();
+        let history = snapshots
+            .entry(snapshot_key::<T>(&snapshot.aggregate_id))
+            .or_default();
+
+        history.insert(
+            snapshot.version,
+            (snapshot.payload().clone(), snapshot.schema_version),
+        );
+        prune(history, self.retention);
+        Ok(())
+    }
+
+    async fn load_snapshot<T>(
+        &self,
+        aggregate_id: &T::Id,
+    ) -> Result<Option<AggregateSnapshot<T>>, CqrsError>
+    where
+        T: Aggregate,
+    {
+        let snapshots = self.snapshots.lock().unwrap();
+
+        Ok(snapshots
+            .get(&snapshot_key::<T>(aggregate_id))
+            .and_then(History::last_key_value)
+            .map(|entry| restore(aggregate_id, entry)))
+    }
+}
@@ -0,0 +1,10 @@
+mod memory;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use memory::InMemorySnapshotStore;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteSnapshotStore;
+
+// The number of snapshots kept per aggregate, unless set otherwise.
+const DEFAULT_RETENTION: usize = 3;
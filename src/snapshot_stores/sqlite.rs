@@ -0,0 +1,194 @@
+use sqlx::SqlitePool;
+
+use super::DEFAULT_RETENTION;
+use crate::{Aggregate, AggregateSnapshot, CqrsError, SnapshotStore};
+
+type SnapshotRow = (String, i64, i64);
+
+/// A [`SnapshotStore`] backed by SQLite via sqlx, available with the `sqlite` feature.
+///
+/// Snapshots of any number of aggregate types are kept in a single `snapshots` table, keyed by
+/// aggregate type, ID and version. The most recent snapshots of each aggregate (3 by default)
+/// are kept; older ones are pruned in the same transaction a new one is saved in.
+///
+/// ```rust,ignore
+/// let snapshot_store = SqliteSnapshotStore::new(pool.clone()).with_retention(5);
+/// snapshot_store.create_table().await?;
+/// ```
+#[derive(Clone)]
+pub struct SqliteSnapshotStore {
+    pool: SqlitePool,
+    retention: usize,
+}
+
+impl SqliteSnapshotStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            retention: DEFAULT_RETENTION,
+        }
+    }
+
+    /// Sets how many snapshots are kept per aggregate. At least one is always kept.
+    pub fn with_retention(mut self, retention: usize) -> Self {
+        self.retention = retention.max(1);
+        self
+    }
+
+    /// Creates the `snapshots` table if it doesn't exist.
+    pub async fn create_table(&self) -> Result<(), CqrsError> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS snapshots (
+                aggregate_type TEXT NOT NULL,
+                aggregate_id TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                schema_version INTEGER NOT NULL,
+                payload TEXT NOT NULL,
+                PRIMARY KEY (aggregate_type, aggregate_id, version)
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(snapshot_store_error)?;
+
+        Ok(())
+    }
+
+    /// Returns the snapshots kept for an aggregate, latest first.
+    pub async fn history<T: Aggregate>(
+        &self,
+        aggregate_id: &T::Id,
+    ) -> Result<Vec<AggregateSnapshot<T>>, CqrsError> {
+        let rows: Vec<SnapshotRow> = sqlx::query_as(
+            "SELECT payload, version, schema_version FROM snapshots
+             WHERE aggregate_type = ? AND aggregate_id = ?
+             ORDER BY version DESC",
+        )
+        .bind(std::any::type_name::<T>())
+        .bind(aggregate_id.to_string())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(snapshot_store_error)?;
+
+        rows.into_iter()
+            .map(|row| restore(aggregate_id, row))
+            .collect()
+    }
+
+    /// Removes all but the `keep` latest snapshots of an aggregate. Returns the number of
+    /// removed snapshots.
+    pub async fn prune<T: Aggregate>(
+        &self,
+        aggregate_id: &T::Id,
+        keep: usize,
+    ) -> Result<usize, CqrsError> {
+        prune(
+            &self.pool,
+            std::any::type_name::<T>(),
+            &aggregate_id.to_string(),
+            keep,
+        )
+        .await
+    }
+}
+
+fn snapshot_store_error(error: sqlx::Error) -> CqrsError {
+    CqrsError::SnapshotStore(error.to_string())
+}
+
+fn restore<T: Aggregate>(
+    aggregate_id: &T::Id,
+    (payload, version, schema_version): SnapshotRow,
+) -> Result<AggregateSnapshot<T>, CqrsError> {
+    let payload = serde_json::from_str(&payload).map_err(|e| {
+        CqrsError::SnapshotStore(format!("Failed to parse snapshot payload JSON: {e}"))
+    })?;
+
+    Ok(AggregateSnapshot::from_stored(
+        aggregate_id.clone(),
+        payload,
+        version as u64,
+        schema_version as u32,
+    ))
+}
+
+async fn prune<'e, E>(
+    executor: E,
+    aggregate_type: &str,
+    aggregate_id: &str,
+    keep: usize,
+) -> Result<usize, CqrsError>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
+    let result = sqlx::query(
+        "DELETE FROM snapshots
+         WHERE aggregate_type = ?1 AND aggregate_id = ?2 AND version NOT IN (
+             SELECT version FROM snapshots
+             WHERE aggregate_type = ?1 AND aggregate_id = ?2
+             ORDER BY version DESC
+             LIMIT ?3
+         )",
+    )
+    .bind(aggregate_type)
+    .bind(aggregate_id)
+    .bind(keep as i64)
+    .execute(executor)
+    .await
+    .map_err(snapshot_store_error)?;
+
+    Ok(result.rows_affected() as usize)
+}
+
+impl SnapshotStore for SqliteSnapshotStore {
+    async fn save_snapshot<T>(&self, snapshot: AggregateSnapshot<T>) -> Result<(), CqrsError>
+    where
+        T: Aggregate,
+    {
+        let aggregate_type = std::any::type_name::<T>();
+        let aggregate_id = snapshot.aggregate_id.to_string();
+        let payload = serde_json::to_string(snapshot.payload())?;
+
+        let mut tx = self.pool.begin().await.map_err(snapshot_store_error)?;
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO snapshots
+             (aggregate_type, aggregate_id, version, schema_version, payload)
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(aggregate_type)
+        .bind(&aggregate_id)
+        .bind(snapshot.version as i64)
+        .bind(snapshot.schema_version as i64)
+        .bind(payload)
+        .execute(&mut *tx)
+        .await
+        .map_err(snapshot_store_error)?;
+
+        prune(&mut *tx, aggregate_type, &aggregate_id, self.retention).await?;
+
+        tx.commit().await.map_err(snapshot_store_error)
+    }
+
+    async fn load_snapshot<T>(
+        &self,
+        aggregate_id: &T::Id,
+    ) -> Result<Option<AggregateSnapshot<T>>, CqrsError>
+    where
+        T: Aggregate,
+    {
+        let row: Option<SnapshotRow> = sqlx::query_as(
+            "SELECT payload, version, schema_version FROM snapshots
+             WHERE aggregate_type = ? AND aggregate_id = ?
+             ORDER BY version DESC
+             LIMIT 1",
+        )
+        .bind(std::any::type_name::<T>())
+        .bind(aggregate_id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(snapshot_store_error)?;
+
+        row.map(|row| restore(aggregate_id, row)).transpose()
+    }
+}
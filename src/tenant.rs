@@ -0,0 +1,64 @@
+use crate::{CqrsError, EventStore, NewEvent};
+
+/// The `TenantEventStore` trait defines event stores holding the streams of several tenants,
+/// handing out stores scoped to one of them.
+///
+/// A stream belongs to the tenant whose scoped store created it. A store scoped to a tenant:
+/// - records the tenant in the [`EventMetadata::tenant_id`] of the events it appends,
+///   rejecting events naming another tenant;
+/// - fails with [`CqrsError::TenantMismatch`] when loading or appending to a stream of another
+///   tenant, instead of seeing it as missing and letting its ID be claimed again;
+/// - only lists the streams of its tenant with [`EventStore::stream_ids`];
+/// - numbers global sequences per tenant, so that each tenant sees a gapless sequence.
+///
+/// Stores that are not scoped to a tenant keep working across tenants, e.g. for
+/// administration tools.
+///
+/// ```rust,ignore
+/// let event_store = SqliteEventStore::new(pool.clone()).for_tenant("acme");
+/// let aggregate_manager = SimpleAggregateManager::new(event_store.clone());
+/// let cqrs = SimpleCqrs::new(aggregate_manager, event_store, consumers).with_tenant("acme");
+/// ```
+///
+/// [`EventMetadata::tenant_id`]: crate::EventMetadata::tenant_id
+pub trait TenantEventStore: EventStore + Sized {
+    /// Returns a store scoped to `tenant_id`, sharing the storage of this one.
+    fn for_tenant(&self, tenant_id: &str) -> Self;
+
+    /// Returns the tenant the store is scoped to, `None` if it isn't scoped.
+    fn tenant_id(&self) -> Option<&str>;
+
+    /// Checks that data of `aggregate_id` owned by `tenant_id` (a stream, or an event about to
+    /// be appended) can be accessed through this store, failing with
+    /// [`CqrsError::TenantMismatch`] otherwise. Stores that are not scoped accept any tenant.
+    fn check_tenant(&self, aggregate_id: &str, tenant_id: Option<&str>) -> Result<(), CqrsError> {
+        match self.tenant_id() {
+            Some(scope) if tenant_id != Some(scope) => Err(CqrsError::TenantMismatch {
+                aggregate_id: aggregate_id.to_string(),
+                tenant_id: scope.to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records the tenant of the store in the metadata of events about to be appended to
+    /// `aggregate_id`, failing with [`CqrsError::TenantMismatch`] if one of them names another
+    /// tenant. Events keep the tenant they name when the store isn't scoped.
+    fn assign_tenant(
+        &self,
+        aggregate_id: &str,
+        events: &[NewEvent],
+    ) -> Result<Vec<NewEvent>, CqrsError> {
+        events
+            .iter()
+            .map(|event| {
+                let mut event = event.clone();
+                if event.metadata.tenant_id.is_none() {
+                    event.metadata.tenant_id = self.tenant_id().map(str::to_string);
+                }
+                self.check_tenant(aggregate_id, event.metadata.tenant_id.as_deref())?;
+                Ok(event)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,747 @@
+//! A conformance suite for [`EventStore`] implementations.
+//!
+//! Each `check_*` function exercises one part of the contract against fresh streams and
+//! panics with a descriptive message if the store doesn't honor it, so they can be called
+//! from any test. [`check_all`] runs every check of the required contract, and the
+//! [`event_store_conformance!`](crate::event_store_conformance) macro generates one test per
+//! check. The checks of optional capabilities, such as [`check_stream_listing`], are called on
+//! their own by the stores supporting them:
+//!
+//! ```rust,ignore
+//! mini_cqrs_es::event_store_conformance!(postgres_event_store, async {
+//!     PostgresEventStore::connect(&database_url()).await.unwrap()
+//! });
+//! ```
+//!
+//! Streams are identified by random IDs, so the suite can run against a persistent store
+//! without cleaning it up first.
+
+use chrono::{DateTime, Duration, Utc};
+
+use super::{unique_id, TestAggregate, TestEvent};
+use crate::{
+    CqrsError, EventMetadata, EventStore, ExpectedVersion, NewEvent, StoredEvent, TenantEventStore,
+    UnitOfWork,
+};
+
+fn aggregate_type() -> &'static str {
+    std::any::type_name::<TestAggregate>()
+}
+
+// Builds a creation event followed by renames, one second apart from each other.
+fn new_events(count: usize) -> Vec<NewEvent> {
+    let start = DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap_or_default();
+
+    (0..count)
+        .map(|i| {
+            let name = format!("name-{i}");
+            let payload = if i == 0 {
+                TestEvent::Created { name }
+            } else {
+                TestEvent::Renamed { name }
+            };
+
+            let mut metadata = EventMetadata {
+                correlation_id: Some(format!("correlation-{i}")),
+                ..EventMetadata::default()
+            };
+            metadata.extra.insert("index".to_string(), i.into());
+
+            let mut event = NewEvent::from_payload(payload, metadata).unwrap();
+            event.timestamp = start + Duration::seconds(i as i64);
+            event
+        })
+        .collect()
+}
+
+fn versions(events: &[StoredEvent]) -> Vec<u64> {
+    events.iter().map(|e| e.version).collect()
+}
+
+fn is_tenant_mismatch<T>(result: Result<T, CqrsError>) -> bool {
+    matches!(result, Err(CqrsError::TenantMismatch { .. }))
+}
+
+async fn save<ES: EventStore>(
+    store: &ES,
+    aggregate_id: &str,
+    events: &[NewEvent],
+    expected_version: ExpectedVersion,
+) -> Result<Vec<StoredEvent>, CqrsError> {
+    store
+        .save_events(aggregate_type(), aggregate_id, events, expected_version)
+        .await
+}
+
+async fn load<ES: EventStore>(store: &ES, aggregate_id: &str) -> (Vec<StoredEvent>, u64) {
+    store
+        .load_events(aggregate_type(), aggregate_id)
+        .await
+        .expect("loading a stream failed")
+}
+
+/// Runs every check of the required contract, leaving out the optional capabilities.
+pub async fn check_all<ES: EventStore>(store: &ES) {
+    check_missing_stream(store).await;
+    check_version_numbering(store).await;
+    check_optimistic_concurrency(store).await;
+    check_empty_batch(store).await;
+    check_stream_isolation(store).await;
+    check_global_sequence(store).await;
+    check_time_travel(store).await;
+    check_unit_of_work(store).await;
+}
+
+/// Checks that a stream that doesn't exist loads as empty, at version `0`.
+pub async fn check_missing_stream<ES: EventStore>(store: &ES) {
+    let aggregate_id = unique_id();
+
+    let (events, version) = load(store, &aggregate_id).await;
+    assert!(events.is_empty(), "a missing stream must load no events");
+    assert_eq!(version, 0, "a missing stream must be at version 0");
+
+    let version = store
+        .stream_version(aggregate_type(), &aggregate_id)
+        .await
+        .expect("reading the version of a missing stream failed");
+    assert_eq!(version, 0, "a missing stream must be at version 0");
+}
+
+/// Checks that appended events are numbered from `1` without gaps, keep their ID and content,
+/// and are loaded back in order.
+pub async fn check_version_numbering<ES: EventStore>(store: &ES) {
+    let aggregate_id = unique_id();
+    let events = new_events(3);
+
+    let first = save(
+        store,
+        &aggregate_id,
+        &events[..2],
+        ExpectedVersion::NoStream,
+    )
+    .await
+    .expect("appending to a new stream failed");
+    assert_eq!(versions(&first), [1, 2], "events must be numbered from 1");
+
+    let second = save(
+        store,
+        &aggregate_id,
+        &events[2..],
+        ExpectedVersion::Exact(2),
+    )
+    .await
+    .expect("appending to an existing stream failed");
+    assert_eq!(
+        versions(&second),
+        [3],
+        "events must follow the stream version"
+    );
+
+    for (stored, new) in first.iter().chain(second.iter()).zip(events.iter()) {
+        assert_eq!(stored.aggregate_type, aggregate_type());
+        assert_eq!(stored.aggregate_id, aggregate_id);
+        assert_eq!(stored.event_type, new.event_type);
+        assert_eq!(stored.payload, new.payload);
+        assert_eq!(stored.metadata, new.metadata);
+        assert_eq!(
+            stored.id, new.id,
+            "events must keep the ID they were created with"
+        );
+    }
+
+    let (loaded, version) = load(store, &aggregate_id).await;
+    assert_eq!(
+        version, 3,
+        "loading must return the version of the last event"
+    );
+    assert_eq!(
+        versions(&loaded),
+        [1, 2, 3],
+        "events must load in version order"
+    );
+
+    let saved_ids: Vec<&String> = first.iter().chain(second.iter()).map(|e| &e.id).collect();
+    let loaded_ids: Vec<&String> = loaded.iter().map(|e| &e.id).collect();
+    assert_eq!(loaded_ids, saved_ids, "loaded events must keep their IDs");
+
+    let mut unique_ids = loaded_ids.clone();
+    unique_ids.sort();
+    unique_ids.dedup();
+    assert_eq!(
+        unique_ids.len(),
+        loaded_ids.len(),
+        "event IDs must be unique"
+    );
+
+    let version = store
+        .stream_version(aggregate_type(), &aggregate_id)
+        .await
+        .expect("reading the version of a stream failed");
+    assert_eq!(
+        version, 3,
+        "the stream version must be the version of the last event"
+    );
+}
+
+/// Checks that every [`ExpectedVersion`] is enforced, and that rejected appends persist
+/// nothing.
+pub async fn check_optimistic_concurrency<ES: EventStore>(store: &ES) {
+    let aggregate_id = unique_id();
+
+    save(
+        store,
+        &aggregate_id,
+        &new_events(1),
+        ExpectedVersion::NoStream,
+    )
+    .await
+    .expect("appending to a new stream failed");
+
+    let result = save(
+        store,
+        &aggregate_id,
+        &new_events(1),
+        ExpectedVersion::Exact(0),
+    )
+    .await;
+    assert!(
+        matches!(
+            result,
+            Err(CqrsError::Conflict {
+                expected_version: 0,
+                actual_version: 1
+            })
+        ),
+        "a stale expected version must fail with a conflict, got {result:?}"
+    );
+
+    let result = save(
+        store,
+        &aggregate_id,
+        &new_events(1),
+        ExpectedVersion::NoStream,
+    )
+    .await;
+    assert!(
+        matches!(
+            result,
+            Err(CqrsError::Conflict {
+                expected_version: 0,
+                actual_version: 1
+            })
+        ),
+        "creating an existing stream must fail with a conflict, got {result:?}"
+    );
+
+    let result = save(
+        store,
+        &unique_id(),
+        &new_events(1),
+        ExpectedVersion::StreamExists,
+    )
+    .await;
+    assert!(
+        matches!(result, Err(CqrsError::AggregateNotFound(_))),
+        "appending to a missing stream that must exist must fail, got {result:?}"
+    );
+
+    save(
+        store,
+        &aggregate_id,
+        &new_events(1),
+        ExpectedVersion::StreamExists,
+    )
+    .await
+    .expect("appending to an existing stream failed");
+    save(
+        store,
+        &aggregate_id,
+        &new_events(1),
+        ExpectedVersion::Exact(2),
+    )
+    .await
+    .expect("appending at the current version failed");
+    save(store, &aggregate_id, &new_events(1), ExpectedVersion::Any)
+        .await
+        .expect("appending without expected version failed");
+
+    let (loaded, version) = load(store, &aggregate_id).await;
+    assert_eq!(version, 4, "rejected appends must not persist any event");
+    assert_eq!(versions(&loaded), [1, 2, 3, 4]);
+}
+
+/// Checks that appending no events succeeds without changing the stream.
+pub async fn check_empty_batch<ES: EventStore>(store: &ES) {
+    let aggregate_id = unique_id();
+
+    let saved = save(store, &aggregate_id, &[], ExpectedVersion::Any)
+        .await
+        .expect("appending no events to a missing stream failed");
+    assert!(
+        saved.is_empty(),
+        "appending no events must return no events"
+    );
+    assert_eq!(load(store, &aggregate_id).await.1, 0);
+
+    save(
+        store,
+        &aggregate_id,
+        &new_events(1),
+        ExpectedVersion::NoStream,
+    )
+    .await
+    .expect("appending to a new stream failed");
+
+    let saved = save(store, &aggregate_id, &[], ExpectedVersion::Exact(1))
+        .await
+        .expect("appending no events to an existing stream failed");
+    assert!(
+        saved.is_empty(),
+        "appending no events must return no events"
+    );
+    assert_eq!(load(store, &aggregate_id).await.1, 1);
+}
+
+/// Checks that streams are identified by both aggregate type and ID.
+pub async fn check_stream_isolation<ES: EventStore>(store: &ES) {
+    let aggregate_id = unique_id();
+    let other_type = format!("{}::Other", aggregate_type());
+    let events = new_events(2);
+
+    save(store, &aggregate_id, &events, ExpectedVersion::NoStream)
+        .await
+        .expect("appending to a new stream failed");
+
+    let other = store
+        .save_events(
+            &other_type,
+            &aggregate_id,
+            &new_events(1),
+            ExpectedVersion::NoStream,
+        )
+        .await
+        .expect("appending to a stream with the same ID but another type failed");
+    assert_eq!(
+        versions(&other),
+        [1],
+        "streams of different types must be numbered apart"
+    );
+
+    let (loaded, version) = store
+        .load_events(&other_type, &aggregate_id)
+        .await
+        .expect("loading a stream failed");
+    assert_eq!(version, 1);
+    assert!(loaded.iter().all(|e| e.aggregate_type == other_type));
+
+    assert_eq!(load(store, &aggregate_id).await.1, 2);
+    assert_eq!(load(store, &unique_id()).await.1, 0);
+}
+
+/// Checks that the streams of an aggregate type are listed once, in creation order, without
+/// the streams of other types or the ones no event was appended to.
+///
+/// Not part of [`check_all`] nor of the macro, as stores relying on the default
+/// [`EventStore::stream_ids`] don't support listing streams.
+pub async fn check_stream_listing<ES: EventStore>(store: &ES) {
+    let (first_id, second_id, missing_id) = (unique_id(), unique_id(), unique_id());
+    let other_type = format!("{}::Other", aggregate_type());
+
+    save(store, &first_id, &new_events(1), ExpectedVersion::NoStream)
+        .await
+        .expect("appending to a new stream failed");
+    save(store, &second_id, &new_events(1), ExpectedVersion::NoStream)
+        .await
+        .expect("appending to a new stream failed");
+    save(store, &first_id, &new_events(1), ExpectedVersion::Exact(1))
+        .await
+        .expect("appending to an existing stream failed");
+    save(
+        store,
+        &missing_id,
+        &new_events(1),
+        ExpectedVersion::StreamExists,
+    )
+    .await
+    .expect_err("appending to a missing stream that must exist must fail");
+    store
+        .save_events(
+            &other_type,
+            &unique_id(),
+            &new_events(1),
+            ExpectedVersion::NoStream,
+        )
+        .await
+        .expect("appending to a new stream failed");
+
+    let listed = store
+        .stream_ids(aggregate_type())
+        .await
+        .expect("listing streams failed");
+    // Other streams may be left over by previous runs against a persistent store.
+    let ours: Vec<&String> = listed
+        .iter()
+        .filter(|id| [&first_id, &second_id, &missing_id].contains(id))
+        .collect();
+    assert_eq!(
+        ours,
+        [&first_id, &second_id],
+        "streams must be listed once, in creation order, and only if they hold events"
+    );
+
+    let other = store
+        .stream_ids(&other_type)
+        .await
+        .expect("listing streams failed");
+    assert!(
+        !other.contains(&first_id) && !other.contains(&second_id),
+        "streams must be listed under their own aggregate type only"
+    );
+}
+
+/// Checks that global sequences, when the store assigns them, are assigned to every event
+/// and increase in append order across streams.
+pub async fn check_global_sequence<ES: EventStore>(store: &ES) {
+    let (first_id, second_id) = (unique_id(), unique_id());
+    let events = new_events(3);
+
+    let mut saved = save(store, &first_id, &events[..2], ExpectedVersion::NoStream)
+        .await
+        .expect("appending to a new stream failed");
+    saved.extend(
+        save(store, &second_id, &new_events(1), ExpectedVersion::NoStream)
+            .await
+            .expect("appending to a new stream failed"),
+    );
+    saved.extend(
+        save(store, &first_id, &events[2..], ExpectedVersion::Exact(2))
+            .await
+            .expect("appending to an existing stream failed"),
+    );
+
+    let sequences: Vec<Option<i64>> = saved.iter().map(|e| e.global_sequence).collect();
+    if sequences.iter().all(Option::is_none) {
+        return;
+    }
+    assert!(
+        sequences.iter().all(Option::is_some),
+        "global sequences must be assigned to every event or to none, got {sequences:?}"
+    );
+    assert!(
+        sequences.windows(2).all(|pair| pair[0] < pair[1]),
+        "global sequences must increase in append order, got {sequences:?}"
+    );
+
+    let (loaded, _) = load(store, &first_id).await;
+    let loaded: Vec<Option<i64>> = loaded.iter().map(|e| e.global_sequence).collect();
+    assert_eq!(
+        loaded,
+        [sequences[0], sequences[1], sequences[3]],
+        "loaded events must keep their global sequence"
+    );
+}
+
+/// Checks that streams can be loaded up to a version or a point in time, and after a version.
+pub async fn check_time_travel<ES: EventStore>(store: &ES) {
+    let aggregate_id = unique_id();
+
+    let saved = save(
+        store,
+        &aggregate_id,
+        &new_events(3),
+        ExpectedVersion::NoStream,
+    )
+    .await
+    .expect("appending to a new stream failed");
+
+    let (events, version) = store
+        .load_events_to_version(aggregate_type(), &aggregate_id, 2)
+        .await
+        .expect("loading a stream up to a version failed");
+    assert_eq!(versions(&events), [1, 2]);
+    assert_eq!(version, 2);
+
+    let (events, version) = store
+        .load_events_to_version(aggregate_type(), &aggregate_id, 10)
+        .await
+        .expect("loading a stream up to a version failed");
+    assert_eq!(versions(&events), [1, 2, 3]);
+    assert_eq!(version, 3);
+
+    let (events, version) = store
+        .load_events_as_of(aggregate_type(), &aggregate_id, saved[1].timestamp)
+        .await
+        .expect("loading a stream as of a timestamp failed");
+    assert_eq!(
+        versions(&events),
+        [1, 2],
+        "events at the timestamp must be included"
+    );
+    assert_eq!(version, 2);
+
+    let (events, version) = store
+        .load_events_as_of(
+            aggregate_type(),
+            &aggregate_id,
+            saved[0].timestamp - Duration::seconds(1),
+        )
+        .await
+        .expect("loading a stream as of a timestamp failed");
+    assert!(events.is_empty());
+    assert_eq!(version, 0);
+
+    let (events, version) = store
+        .load_events_after(aggregate_type(), &aggregate_id, 1)
+        .await
+        .expect("loading a stream after a version failed");
+    assert_eq!(versions(&events), [2, 3]);
+    assert_eq!(version, 3);
+
+    let (events, version) = store
+        .load_events_after(aggregate_type(), &aggregate_id, 3)
+        .await
+        .expect("loading a stream after a version failed");
+    assert!(events.is_empty());
+    assert_eq!(
+        version, 3,
+        "the current version must be returned when no event follows"
+    );
+}
+
+/// Checks that deleting the events preceding a version keeps the following ones and the
+/// stream version, so that appends keep being checked against it.
+///
+/// Not part of [`check_all`] nor of the macro, as stores relying on the default
+/// [`EventStore::delete_events_before`] don't support deleting events.
+pub async fn check_truncation<ES: EventStore>(store: &ES) {
+    let aggregate_id = unique_id();
+
+    save(
+        store,
+        &aggregate_id,
+        &new_events(3),
+        ExpectedVersion::NoStream,
+    )
+    .await
+    .expect("appending to a new stream failed");
+
+    let deleted = store
+        .delete_events_before(aggregate_type(), &aggregate_id, 3)
+        .await
+        .expect("deleting events failed");
+    assert_eq!(
+        deleted, 2,
+        "the events preceding the version must be deleted"
+    );
+
+    let (events, version) = load(store, &aggregate_id).await;
+    assert_eq!(
+        versions(&events),
+        [3],
+        "the event at the version must be kept"
+    );
+    assert_eq!(version, 3, "a truncated stream must keep its version");
+
+    let result = save(
+        store,
+        &aggregate_id,
+        &new_events(1),
+        ExpectedVersion::Exact(1),
+    )
+    .await;
+    assert!(
+        matches!(result, Err(CqrsError::Conflict { .. })),
+        "a truncated stream must keep checking expected versions, got {result:?}"
+    );
+    let saved = save(
+        store,
+        &aggregate_id,
+        &new_events(1),
+        ExpectedVersion::Exact(3),
+    )
+    .await
+    .expect("appending to a truncated stream failed");
+    assert_eq!(versions(&saved), [4]);
+}
+
+/// Checks that units of work are committed atomically. Stores that don't support units of
+/// work spanning several streams must reject them without persisting anything.
+pub async fn check_unit_of_work<ES: EventStore>(store: &ES) {
+    let (first_id, second_id) = (unique_id(), unique_id());
+
+    let unit = UnitOfWork::new().append_for::<TestAggregate>(
+        &first_id,
+        new_events(1),
+        ExpectedVersion::NoStream,
+    );
+    let saved = store
+        .save_unit_of_work(&unit)
+        .await
+        .expect("committing a unit of work with a single append failed");
+    assert_eq!(
+        saved.len(),
+        1,
+        "a unit of work must return the events of each append"
+    );
+    assert_eq!(versions(&saved[0]), [1]);
+
+    // The second append conflicts, so the first one must not be persisted either.
+    let unit = UnitOfWork::new()
+        .append_for::<TestAggregate>(&second_id, new_events(2), ExpectedVersion::NoStream)
+        .append_for::<TestAggregate>(&first_id, new_events(2), ExpectedVersion::NoStream);
+    let result = store.save_unit_of_work(&unit).await;
+    assert!(
+        result.is_err(),
+        "a unit of work with a conflicting append must fail"
+    );
+
+    assert_eq!(
+        load(store, &second_id).await.1,
+        0,
+        "units of work must be atomic"
+    );
+    assert_eq!(
+        load(store, &first_id).await.1,
+        1,
+        "units of work must be atomic"
+    );
+}
+
+/// Checks that a [`TenantEventStore`] isolates the streams of each tenant and numbers their
+/// global sequences separately.
+///
+/// Not part of [`check_all`] nor of the macro, as it requires a store that can be scoped to
+/// tenants.
+pub async fn check_tenant_isolation<ES: TenantEventStore>(store: &ES) {
+    let (first, second) = (
+        store.for_tenant(&unique_id()),
+        store.for_tenant(&unique_id()),
+    );
+    let (first_id, second_id) = (unique_id(), unique_id());
+
+    let saved = save(&first, &first_id, &new_events(2), ExpectedVersion::NoStream)
+        .await
+        .expect("appending to a new stream failed");
+    assert!(
+        saved
+            .iter()
+            .all(|e| e.metadata.tenant_id.as_deref() == first.tenant_id()),
+        "appended events must record the tenant of the store"
+    );
+    let other = save(
+        &second,
+        &second_id,
+        &new_events(1),
+        ExpectedVersion::NoStream,
+    )
+    .await
+    .expect("appending to a new stream failed");
+
+    if saved[0].global_sequence.is_some() {
+        let sequences: Vec<Option<i64>> = saved.iter().map(|e| e.global_sequence).collect();
+        assert_eq!(
+            sequences,
+            [Some(1), Some(2)],
+            "global sequences must be numbered per tenant"
+        );
+        assert_eq!(
+            other[0].global_sequence,
+            Some(1),
+            "global sequences must be numbered per tenant"
+        );
+    }
+
+    let (events, version) = load(&first, &first_id).await;
+    assert_eq!(
+        (versions(&events), version),
+        (vec![1, 2], 2),
+        "a tenant must load its own streams"
+    );
+
+    assert!(
+        is_tenant_mismatch(second.load_events(aggregate_type(), &first_id).await),
+        "loading a stream of another tenant must fail with TenantMismatch"
+    );
+    assert!(
+        is_tenant_mismatch(second.stream_version(aggregate_type(), &first_id).await),
+        "reading the version of a stream of another tenant must fail with TenantMismatch"
+    );
+    assert!(
+        is_tenant_mismatch(save(&second, &first_id, &new_events(1), ExpectedVersion::Any).await),
+        "appending to a stream of another tenant must fail with TenantMismatch"
+    );
+    assert!(
+        is_tenant_mismatch(
+            save(
+                &second,
+                &first_id,
+                &new_events(1),
+                ExpectedVersion::NoStream
+            )
+            .await
+        ),
+        "creating a stream of another tenant must fail with TenantMismatch"
+    );
+
+    let mut foreign = new_events(1);
+    foreign[0].metadata.tenant_id = second.tenant_id().map(str::to_string);
+    assert!(
+        is_tenant_mismatch(save(&first, &unique_id(), &foreign, ExpectedVersion::NoStream).await),
+        "appending events of another tenant must fail with TenantMismatch"
+    );
+    assert_eq!(
+        load(&first, &first_id).await.1,
+        2,
+        "rejected appends must not be persisted"
+    );
+
+    if let Ok(listed) = first.stream_ids(aggregate_type()).await {
+        assert_eq!(
+            listed,
+            [first_id],
+            "a tenant must only list its own streams"
+        );
+    }
+}
+
+/// Generates a module of tests running the [`EventStore`] conformance suite, one test per
+/// check.
+///
+/// `$store` is an expression evaluating to a future that resolves to a store, evaluated once
+/// per test within the generated module (which imports everything from its parent). The
+/// tests run on Tokio, which must be a dev-dependency with the `macros` and `rt` features.
+///
+/// ```rust,ignore
+/// mini_cqrs_es::event_store_conformance!(in_memory_event_store, async {
+///     InMemoryEventStore::new()
+/// });
+/// ```
+#[macro_export]
+macro_rules! event_store_conformance {
+    ($name:ident, $store:expr) => {
+        mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            $crate::event_store_conformance!(@tests $store;
+                check_missing_stream,
+                check_version_numbering,
+                check_optimistic_concurrency,
+                check_empty_batch,
+                check_stream_isolation,
+                check_global_sequence,
+                check_time_travel,
+                check_unit_of_work,
+            );
+        }
+    };
+    (@tests $store:expr; $($check:ident,)*) => {
+        $(
+            #[::tokio::test]
+            async fn $check() {
+                let store = $store.await;
+                $crate::testkit::event_store::$check(&store).await;
+            }
+        )*
+    };
+}
@@ -0,0 +1,82 @@
+//! Test utilities for applications and libraries built on MiniCQRS/ES, available with the
+//! `testkit` feature.
+//!
+//! - [`event_store`] and [`snapshot_store`] contain conformance suites checking that custom
+//!   [`EventStore`](crate::EventStore) and [`SnapshotStore`](crate::SnapshotStore)
+//!   implementations honor the contract of the traits.
+//! - [`ProjectionHarness`] feeds typed domain events to an
+//!   [`EventConsumer`](crate::EventConsumer), to test projections.
+
+pub mod event_store;
+mod projection;
+pub mod snapshot_store;
+
+pub use projection::ProjectionHarness;
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Aggregate, CqrsError, EventPayload};
+
+// The events appended by the conformance suites.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum TestEvent {
+    Created { name: String },
+    Renamed { name: String },
+}
+
+impl fmt::Display for TestEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestEvent::Created { .. } => write!(f, "Created"),
+            TestEvent::Renamed { .. } => write!(f, "Renamed"),
+        }
+    }
+}
+
+impl EventPayload for TestEvent {}
+
+// The aggregate saved and loaded by the conformance suites.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct TestAggregate {
+    id: String,
+    version: u64,
+    name: String,
+}
+
+impl Aggregate for TestAggregate {
+    type Id = String;
+    type Event = TestEvent;
+
+    async fn apply(&mut self, event: &Self::Event) -> Result<(), CqrsError> {
+        match event {
+            TestEvent::Created { name } | TestEvent::Renamed { name } => {
+                self.name = name.clone();
+            }
+        }
+        Ok(())
+    }
+
+    fn aggregate_id(&self) -> Self::Id {
+        self.id.clone()
+    }
+
+    fn set_aggregate_id(&mut self, id: Self::Id) {
+        self.id = id;
+    }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn set_version(&mut self, version: u64) {
+        self.version = version;
+    }
+}
+
+// Returns an ID that no previous run of a suite used, so suites can run against persistent
+// stores without cleaning them up first.
+fn unique_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
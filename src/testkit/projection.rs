@@ -0,0 +1,145 @@
+//! A harness feeding typed domain events to an [`EventConsumer`], to test projections
+//! without building [`StoredEvent`]s by hand.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    Aggregate, CqrsError, EventConsumer, EventMetadata, EventPayload, IdGenerator, StoredEvent,
+    UuidGenerator,
+};
+
+/// Feeds domain events through an [`EventConsumer`] as properly numbered envelopes.
+///
+/// Each stream is numbered from version `1`, and events get a global sequence increasing
+/// across streams, an ID from the [`IdGenerator`] of the harness ([`UuidGenerator`] by
+/// default), and a timestamp one second after the previous event's.
+///
+/// ```rust,ignore
+/// let read_model = Arc::new(Mutex::new(HotelReadModel::default()));
+/// let mut harness = ProjectionHarness::new(HotelProjectionConsumer::new(read_model.clone()));
+///
+/// harness
+///     .given::<HotelAggregate>(&hotel_id, [
+///         HotelEvent::HotelInitialized { room_count: 2 },
+///         HotelEvent::GuestCheckedIn {
+///             room_number: 1,
+///             stay_id: "1-2".into(),
+///             guest_name: "Alice".into(),
+///         },
+///     ])
+///     .await?;
+///
+/// assert_eq!(read_model.lock().unwrap().rooms.len(), 2);
+/// ```
+pub struct ProjectionHarness<C>
+where
+    C: EventConsumer,
+{
+    consumer: C,
+    metadata: EventMetadata,
+    id_generator: Box<dyn IdGenerator>,
+    versions: HashMap<(&'static str, String), u64>,
+    events: Vec<StoredEvent>,
+    next_timestamp: DateTime<Utc>,
+}
+
+impl<C> ProjectionHarness<C>
+where
+    C: EventConsumer,
+{
+    /// Creates a new harness, timestamping the first event with the current time.
+    pub fn new(consumer: C) -> Self {
+        Self {
+            consumer,
+            metadata: EventMetadata::default(),
+            id_generator: Box::new(UuidGenerator),
+            versions: HashMap::new(),
+            events: Vec::new(),
+            next_timestamp: Utc::now(),
+        }
+    }
+
+    /// Sets the timestamp of the next event.
+    pub fn with_start_time(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.next_timestamp = timestamp;
+        self
+    }
+
+    /// Sets the generator of the IDs of the next events. Defaults to [`UuidGenerator`].
+    pub fn with_id_generator(mut self, id_generator: impl IdGenerator + 'static) -> Self {
+        self.id_generator = Box::new(id_generator);
+        self
+    }
+
+    /// Sets the metadata attached to the next events.
+    pub fn with_metadata(mut self, metadata: EventMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Appends events to the stream of the aggregate `A` identified by `aggregate_id`, and
+    /// feeds them through the consumer in order. Returns the envelopes that were fed.
+    ///
+    /// Stops at the first event the consumer fails to process, returning its error; the
+    /// events fed before it stay recorded.
+    pub async fn given<A>(
+        &mut self,
+        aggregate_id: &A::Id,
+        events: impl IntoIterator<Item = A::Event>,
+    ) -> Result<Vec<StoredEvent>, CqrsError>
+    where
+        A: Aggregate,
+    {
+        let aggregate_type = std::any::type_name::<A>();
+        let aggregate_id = aggregate_id.to_string();
+        let mut fed = Vec::new();
+
+        for payload in events {
+            let version = self
+                .versions
+                .entry((aggregate_type, aggregate_id.clone()))
+                .or_default();
+            *version += 1;
+
+            let event = StoredEvent {
+                id: self.id_generator.next_id(),
+                aggregate_id: aggregate_id.clone(),
+                aggregate_type: aggregate_type.to_string(),
+                version: *version,
+                event_type: payload.name(),
+                payload: serde_json::to_value(&payload)?,
+                metadata: self.metadata.clone(),
+                global_sequence: Some(self.events.len() as i64 + 1),
+                timestamp: self.next_timestamp,
+            };
+            self.next_timestamp += Duration::seconds(1);
+            self.events.push(event.clone());
+
+            self.consumer.process(&event).await?;
+            fed.push(event);
+        }
+
+        Ok(fed)
+    }
+
+    /// Returns the current version of the stream of the aggregate `A`, `0` if no event was
+    /// fed to it.
+    pub fn version<A: Aggregate>(&self, aggregate_id: &A::Id) -> u64 {
+        self.versions
+            .get(&(std::any::type_name::<A>(), aggregate_id.to_string()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Returns every envelope fed so far, across streams, in order.
+    pub fn events(&self) -> &[StoredEvent] {
+        &self.events
+    }
+
+    /// Returns the consumer under test.
+    pub fn consumer(&self) -> &C {
+        &self.consumer
+    }
+}
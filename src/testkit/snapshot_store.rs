@@ -0,0 +1,167 @@
+//! A conformance suite for [`SnapshotStore`] implementations.
+//!
+//! Like the [event store suite](super::event_store), each `check_*` function panics with a
+//! descriptive message if the store doesn't honor the contract, [`check_all`] runs every
+//! check, and the [`snapshot_store_conformance!`](crate::snapshot_store_conformance) macro
+//! generates one test per check.
+
+use super::{unique_id, TestAggregate};
+use crate::{Aggregate, AggregateSnapshot, SnapshotStore};
+
+fn aggregate(id: &str, version: u64, name: &str) -> TestAggregate {
+    TestAggregate {
+        id: id.to_string(),
+        version,
+        name: name.to_string(),
+    }
+}
+
+async fn save<SS: SnapshotStore>(store: &SS, aggregate: &TestAggregate) {
+    let snapshot = AggregateSnapshot::new(aggregate, Some(aggregate.version)).unwrap();
+    store
+        .save_snapshot(snapshot)
+        .await
+        .expect("saving a snapshot failed");
+}
+
+async fn load<SS: SnapshotStore>(store: &SS, id: &str) -> Option<(TestAggregate, u64)> {
+    store
+        .load_snapshot::<TestAggregate>(&id.to_string())
+        .await
+        .expect("loading a snapshot failed")
+        .map(|snapshot| {
+            let aggregate = snapshot
+                .get_payload::<TestAggregate>()
+                .expect("a snapshot payload can't be deserialized");
+            (aggregate, snapshot.version)
+        })
+}
+
+/// Runs every check of the suite.
+pub async fn check_all<SS: SnapshotStore>(store: &SS) {
+    check_missing_snapshot(store).await;
+    check_roundtrip(store).await;
+    check_latest_snapshot_wins(store).await;
+    check_snapshot_isolation(store).await;
+    check_schema_version(store).await;
+}
+
+/// Checks that loading the snapshot of an aggregate that has none returns `None`.
+pub async fn check_missing_snapshot<SS: SnapshotStore>(store: &SS) {
+    assert!(
+        load(store, &unique_id()).await.is_none(),
+        "a missing snapshot must load as None"
+    );
+}
+
+/// Checks that a snapshot loads back with the same state and version.
+pub async fn check_roundtrip<SS: SnapshotStore>(store: &SS) {
+    let saved = aggregate(&unique_id(), 3, "roundtrip");
+    save(store, &saved).await;
+
+    let (loaded, version) = load(store, &saved.id)
+        .await
+        .expect("a saved snapshot must load");
+    assert_eq!(loaded, saved, "a snapshot must keep the aggregate state");
+    assert_eq!(version, 3, "a snapshot must keep its version");
+}
+
+/// Checks that saving a newer snapshot of an aggregate replaces the previous one.
+pub async fn check_latest_snapshot_wins<SS: SnapshotStore>(store: &SS) {
+    let id = unique_id();
+    save(store, &aggregate(&id, 2, "old")).await;
+    save(store, &aggregate(&id, 5, "new")).await;
+
+    let (loaded, version) = load(store, &id).await.expect("a saved snapshot must load");
+    assert_eq!(loaded.name, "new", "the latest snapshot must be loaded");
+    assert_eq!(version, 5, "the latest snapshot must be loaded");
+}
+
+/// Checks that snapshots of different aggregates don't overwrite each other.
+pub async fn check_snapshot_isolation<SS: SnapshotStore>(store: &SS) {
+    let (first, second) = (
+        aggregate(&unique_id(), 1, "first"),
+        aggregate(&unique_id(), 2, "second"),
+    );
+    save(store, &first).await;
+    save(store, &second).await;
+
+    assert_eq!(load(store, &first.id).await.map(|(a, _)| a), Some(first));
+    assert_eq!(load(store, &second.id).await.map(|(a, _)| a), Some(second));
+}
+
+/// Checks that snapshots keep their schema version, and that snapshots of another schema
+/// load back as they were saved, even if they no longer deserialize into the aggregate.
+pub async fn check_schema_version<SS: SnapshotStore>(store: &SS) {
+    let id = unique_id();
+    save(store, &aggregate(&id, 1, "current")).await;
+
+    let snapshot = store
+        .load_snapshot::<TestAggregate>(&id)
+        .await
+        .expect("loading a snapshot failed")
+        .expect("a saved snapshot must load");
+    assert_eq!(
+        snapshot.schema_version,
+        TestAggregate::SCHEMA_VERSION,
+        "a snapshot must keep its schema version"
+    );
+
+    let legacy_payload = serde_json::json!({ "title": "legacy" });
+    let legacy = AggregateSnapshot::<TestAggregate>::from_stored(
+        id.clone(),
+        legacy_payload.clone(),
+        2,
+        TestAggregate::SCHEMA_VERSION + 1,
+    );
+    store
+        .save_snapshot(legacy)
+        .await
+        .expect("saving a snapshot of another schema failed");
+
+    let snapshot = store
+        .load_snapshot::<TestAggregate>(&id)
+        .await
+        .expect("a snapshot of another schema must load without being deserialized")
+        .expect("a saved snapshot must load");
+    assert_eq!(snapshot.payload(), &legacy_payload);
+    assert_eq!(snapshot.version, 2);
+    assert_eq!(snapshot.schema_version, TestAggregate::SCHEMA_VERSION + 1);
+    assert!(!snapshot.is_compatible());
+}
+
+/// Generates a module of tests running the [`SnapshotStore`] conformance suite, one test per
+/// check. See [`event_store_conformance!`](crate::event_store_conformance) for the
+/// requirements.
+///
+/// ```rust,ignore
+/// mini_cqrs_es::snapshot_store_conformance!(redis_snapshot_store, async {
+///     RedisSnapshotStore::connect(&redis_url()).await.unwrap()
+/// });
+/// ```
+#[macro_export]
+macro_rules! snapshot_store_conformance {
+    ($name:ident, $store:expr) => {
+        mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            $crate::snapshot_store_conformance!(@tests $store;
+                check_missing_snapshot,
+                check_roundtrip,
+                check_latest_snapshot_wins,
+                check_snapshot_isolation,
+                check_schema_version,
+            );
+        }
+    };
+    (@tests $store:expr; $($check:ident,)*) => {
+        $(
+            #[::tokio::test]
+            async fn $check() {
+                let store = $store.await;
+                $crate::testkit::snapshot_store::$check(&store).await;
+            }
+        )*
+    };
+}
@@ -0,0 +1,81 @@
+use crate::{Aggregate, ExpectedVersion, NewEvent};
+
+/// A batch of events to append to a single stream as part of a [`UnitOfWork`].
+#[derive(Clone, Debug)]
+pub struct StreamAppend {
+    pub aggregate_type: String,
+    pub aggregate_id: String,
+    pub events: Vec<NewEvent>,
+    pub expected_version: ExpectedVersion,
+}
+
+/// A set of appends to several streams that must be committed atomically.
+///
+/// Event stores commit a unit of work through
+/// [`EventStore::save_unit_of_work`](crate::EventStore::save_unit_of_work): either every
+/// append succeeds, or none of them is persisted. Appends are applied in the order they were
+/// added, so the same stream can appear more than once as long as the expected versions
+/// account for the previous appends.
+///
+/// ```rust,ignore
+/// let unit = UnitOfWork::new()
+///     .append_for::<HotelAggregate>(&from, vec![checked_out], ExpectedVersion::Exact(3))
+///     .append_for::<HotelAggregate>(&to, vec![checked_in], ExpectedVersion::Exact(7));
+///
+/// let persisted = event_store.save_unit_of_work(&unit).await?;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct UnitOfWork {
+    appends: Vec<StreamAppend>,
+}
+
+impl UnitOfWork {
+    pub fn new() -> Self {
+        Self {
+            appends: Vec::new(),
+        }
+    }
+
+    /// Adds an append to the stream identified by `aggregate_type` and `aggregate_id`.
+    pub fn append(
+        mut self,
+        aggregate_type: impl Into<String>,
+        aggregate_id: impl Into<String>,
+        events: Vec<NewEvent>,
+        expected_version: impl Into<ExpectedVersion>,
+    ) -> Self {
+        self.appends.push(StreamAppend {
+            aggregate_type: aggregate_type.into(),
+            aggregate_id: aggregate_id.into(),
+            events,
+            expected_version: expected_version.into(),
+        });
+        self
+    }
+
+    /// Adds an append to the stream of the aggregate `A` identified by `aggregate_id`, using
+    /// the same stream naming as the aggregate managers.
+    pub fn append_for<A: Aggregate>(
+        self,
+        aggregate_id: &A::Id,
+        events: Vec<NewEvent>,
+        expected_version: impl Into<ExpectedVersion>,
+    ) -> Self {
+        self.append(
+            std::any::type_name::<A>(),
+            aggregate_id.to_string(),
+            events,
+            expected_version,
+        )
+    }
+
+    /// Returns the appends in the order they were added.
+    pub fn appends(&self) -> &[StreamAppend] {
+        &self.appends
+    }
+
+    /// Returns `true` if the unit of work contains no appends.
+    pub fn is_empty(&self) -> bool {
+        self.appends.is_empty()
+    }
+}
@@ -0,0 +1,273 @@
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::archive::check_history;
+use crate::{Aggregate, AggregateSnapshot, CqrsError, EventStore, SnapshotStore, StoredEvent};
+
+/// What a replayed state was compared against when it diverged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DivergenceKind {
+    /// Two replays of the same events produced different states, e.g. because `apply` reads
+    /// the clock or depends on the iteration order of a hash map.
+    Replay,
+    /// The stored snapshot doesn't match the state replayed from the events up to its version.
+    Snapshot,
+}
+
+/// A divergence found by a [`ReplayVerifier`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    /// The ID of the aggregate whose stream diverged.
+    pub aggregate_id: String,
+    /// What the replayed state was compared against.
+    pub kind: DivergenceKind,
+    /// The first version at which the states differ.
+    pub version: u64,
+    /// The serialized state replayed from the events (by the first replay, for replay
+    /// divergences), or `null` if the snapshot is ahead of the stream.
+    pub expected: Value,
+    /// The serialized state of the second replay, or of the snapshot.
+    pub actual: Value,
+}
+
+/// The outcome of verifying every stream of an aggregate type.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayReport {
+    /// The number of verified streams.
+    pub streams: usize,
+    /// The number of replayed events, across streams.
+    pub events: usize,
+    /// The divergences found, at most one of each kind per stream.
+    pub divergences: Vec<Divergence>,
+}
+
+impl ReplayReport {
+    /// Returns `true` if no divergence was found.
+    pub fn is_consistent(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// A [`SnapshotStore`] holding no snapshots, used by [`ReplayVerifier`] until a snapshot
+/// store is set.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoSnapshotStore;
+
+impl SnapshotStore for NoSnapshotStore {
+    async fn save_snapshot<T>(&self, _aggregate: AggregateSnapshot<T>) -> Result<(), CqrsError>
+    where
+        T: Aggregate,
+    {
+        Ok(())
+    }
+
+    async fn load_snapshot<T>(
+        &self,
+        _aggregate_id: &T::Id,
+    ) -> Result<Option<AggregateSnapshot<T>>, CqrsError>
+    where
+        T: Aggregate,
+    {
+        Ok(None)
+    }
+}
+
+/// Checks that aggregates are rebuilt deterministically from their events.
+///
+/// Each stream is replayed twice, event by event, and the serialized states of both replays
+/// are compared after every event, reporting the first version at which they differ. When a
+/// snapshot store is set, the stored snapshot is compared with the state replayed up to its
+/// version too, unless it was taken with another schema version.
+///
+/// Streams truncated by a [`StreamArchiver`] can't be replayed from the event store and fail
+/// with [`CqrsError::TruncatedStream`].
+///
+/// Verification is meant to run offline (e.g. in CI against a copy of production data), as
+/// it loads and serializes every state of every stream.
+///
+/// ```rust,ignore
+/// let verifier = ReplayVerifier::new(event_store).with_snapshot_store(snapshot_store);
+///
+/// let report = verifier.verify_all::<HotelAggregate>().await?;
+/// for divergence in &report.divergences {
+///     eprintln!("{} diverges at version {}", divergence.aggregate_id, divergence.version);
+/// }
+/// ```
+///
+/// [`StreamArchiver`]: crate::StreamArchiver
+pub struct ReplayVerifier<ES, SS = NoSnapshotStore>
+where
+    ES: EventStore,
+    SS: SnapshotStore,
+{
+    event_store: ES,
+    snapshot_store: SS,
+}
+
+impl<ES> ReplayVerifier<ES>
+where
+    ES: EventStore,
+{
+    /// Creates a new verifier, replaying events without comparing them against snapshots.
+    pub fn new(event_store: ES) -> Self {
+        Self {
+            event_store,
+            snapshot_store: NoSnapshotStore,
+        }
+    }
+}
+
+impl<ES, SS> ReplayVerifier<ES, SS>
+where
+    ES: EventStore,
+    SS: SnapshotStore,
+{
+    /// Sets the snapshot store whose snapshots are compared with the replayed states.
+    pub fn with_snapshot_store<S>(self, snapshot_store: S) -> ReplayVerifier<ES, S>
+    where
+        S: SnapshotStore,
+    {
+        ReplayVerifier {
+            event_store: self.event_store,
+            snapshot_store,
+        }
+    }
+
+    /// Verifies every stream of the aggregate `A`, as listed by [`EventStore::stream_ids`].
+    ///
+    /// Divergences are reported rather than returned as errors; errors are reserved for store
+    /// failures and events that can't be applied.
+    pub async fn verify_all<A>(&self) -> Result<ReplayReport, CqrsError>
+    where
+        A: Aggregate,
+    {
+        let aggregate_type = std::any::type_name::<A>();
+        let stream_ids = self
+            .event_store
+            .stream_ids(aggregate_type)
+            .await
+            .map_err(CqrsError::into_event_store)?;
+
+        let mut report = ReplayReport::default();
+        for stream_id in stream_ids {
+            let aggregate_id = A::Id::from_str(&stream_id).map_err(|_| {
+                CqrsError::EventStore(format!(
+                    "invalid aggregate id `{stream_id}` for {aggregate_type}"
+                ))
+            })?;
+
+            let (events, divergences) = self.verify_stream::<A>(&aggregate_id).await?;
+            report.streams += 1;
+            report.events += events;
+            report.divergences.extend(divergences);
+        }
+
+        Ok(report)
+    }
+
+    /// Verifies the stream of a single aggregate, returning the divergences found.
+    pub async fn verify<A>(&self, aggregate_id: &A::Id) -> Result<Vec<Divergence>, CqrsError>
+    where
+        A: Aggregate,
+    {
+        let (_, divergences) = self.verify_stream::<A>(aggregate_id).await?;
+        Ok(divergences)
+    }
+
+    // Replays a stream twice in lockstep. Returns the number of events and the divergences.
+    async fn verify_stream<A>(
+        &self,
+        aggregate_id: &A::Id,
+    ) -> Result<(usize, Vec<Divergence>), CqrsError>
+    where
+        A: Aggregate,
+    {
+        let (events, stream_version) = self
+            .event_store
+            .load_events(std::any::type_name::<A>(), &aggregate_id.to_string())
+            .await
+            .map_err(CqrsError::into_event_store)?;
+        check_history(aggregate_id, &events)?;
+
+        let snapshot = self
+            .snapshot_store
+            .load_snapshot::<A>(aggregate_id)
+            .await
+            .map_err(CqrsError::into_snapshot_store)?
+            // Snapshots of another schema are discarded when loading, not diverging.
+            .filter(AggregateSnapshot::is_compatible)
+            .map(|snapshot| {
+                let mut aggregate = snapshot.get_payload::<A>()?;
+                aggregate.set_version(snapshot.version);
+                Ok::<_, CqrsError>((snapshot.version, serde_json::to_value(&aggregate)?))
+            })
+            .transpose()?;
+
+        let divergence = |kind, version, expected, actual| Divergence {
+            aggregate_id: aggregate_id.to_string(),
+            kind,
+            version,
+            expected,
+            actual,
+        };
+
+        let mut first = A::default();
+        first.set_aggregate_id(aggregate_id.clone());
+        let mut second = A::default();
+        second.set_aggregate_id(aggregate_id.clone());
+
+        let mut divergences = Vec::new();
+        let mut replays_diverged = false;
+
+        for event in &events {
+            let expected = apply_event(&mut first, event).await?;
+            let actual = apply_event(&mut second, event).await?;
+
+            if let Some((version, snapshot)) = &snapshot
+                && *version == event.version
+                && *snapshot != expected
+            {
+                divergences.push(divergence(
+                    DivergenceKind::Snapshot,
+                    event.version,
+                    expected.clone(),
+                    snapshot.clone(),
+                ));
+            }
+
+            if !replays_diverged && expected != actual {
+                replays_diverged = true;
+                divergences.push(divergence(
+                    DivergenceKind::Replay,
+                    event.version,
+                    expected,
+                    actual,
+                ));
+            }
+        }
+
+        if let Some((version, snapshot)) = snapshot
+            && version > stream_version
+        {
+            divergences.push(divergence(
+                DivergenceKind::Snapshot,
+                version,
+                Value::Null,
+                snapshot,
+            ));
+        }
+
+        Ok((events.len(), divergences))
+    }
+}
+
+// Applies a single event and returns the serialized state.
+async fn apply_event<A: Aggregate>(
+    aggregate: &mut A,
+    event: &StoredEvent,
+) -> Result<Value, CqrsError> {
+    aggregate.apply_events(std::slice::from_ref(event)).await?;
+    aggregate.set_version(event.version);
+    Ok(serde_json::to_value(&*aggregate)?)
+}